@@ -0,0 +1,460 @@
+use std::{
+    cell::Cell,
+    ops::Range,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use anyhow::{anyhow, bail};
+use ffmpeg_next::{format, media};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    config::{AudioCodec, Container, ExportProfile, RateControl, VideoCodec},
+    ui::{
+        output::{
+            output::OutputJob,
+            progress::{CancelToken, Cancelled},
+        },
+        player::model::StreamKind,
+    },
+};
+
+/// exit code when arguments are invalid
+const EXIT_USAGE: u8 = 2;
+/// exit code when the export was cancelled
+const EXIT_CANCELLED: u8 = 130;
+
+static USAGE: &str = "\
+usage: fast_clip clip --input <file> [options]
+
+  -i, --input <file>        source video
+      --start <time>        start of range, default start of file
+      --end <time>          end of range, default end of file
+      --range <start-end>   range to export, repeat to concat several ranges
+      --audio <n>           audio track to export, counted from 1, repeat for several
+  -o, --output <file>       output file, default <input>_edit.<input ext>
+      --container <name>    mp4, mkv, mov, webm or ts, default by output extension
+      --transcode           re-encode instead of stream copy
+      --video-codec <name>  h264, h265, vp9 or av1
+      --audio-codec <name>  aac or opus
+      --crf <n>             constant quality of transcode
+      --video-bitrate <k>   video bitrate in kbps, instead of crf
+      --audio-bitrate <k>   audio bitrate in kbps
+      --frame-accurate      re-encode edges of copied range
+      --batch <template>    one file per range, {name} and {index} are replaced
+      --no-extra-streams    drop subtitle, attachment and data streams
+      --strip-metadata      drop tags such as creation time and location
+
+time is seconds or [hh:]mm:ss[.ms], progress is printed to stdout as json lines
+
+usage: fast_clip [file] [--seek <time>] [--range <start-end>]
+
+  opens the window with file loaded, seeked and the A/B range selected";
+
+/// run the command line mode when first argument is a command, gpui is not started
+pub fn run(args: &[String]) -> Option<ExitCode> {
+    match args.first().map(String::as_str) {
+        Some("clip") => Some(clip(&args[1..])),
+        Some("help" | "--help" | "-h") => {
+            println!("{}", USAGE);
+            Some(ExitCode::SUCCESS)
+        }
+        _ => None,
+    }
+}
+
+fn clip(args: &[String]) -> ExitCode {
+    let args = match parse_clip(args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+    let job = match build_job(args) {
+        Ok(job) => job,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // one line per percent is enough for scripts
+    let last = Cell::new(-1.);
+    let on_progress = |value: f64| {
+        let value = (value * 100.).floor() / 100.;
+        if value > last.get() {
+            last.set(value);
+            println!("{}", json!({ "event": "progress", "value": value }));
+        }
+    };
+    // ctrl-c stops the export, partial files are removed before exit
+    let cancel = CancelToken::new();
+    let on_interrupt = cancel.clone();
+    if let Err(e) = ctrlc::set_handler(move || on_interrupt.cancel()) {
+        eprintln!("failed to handle ctrl-c: {}", e);
+    }
+    let results = job.run(&cancel, on_progress);
+
+    let mut code = ExitCode::SUCCESS;
+    for (path, result) in results {
+        match result {
+            Ok(notices) => println!(
+                "{}",
+                json!({ "event": "finished", "output": path, "notices": notices })
+            ),
+            Err(e) => {
+                println!(
+                    "{}",
+                    json!({ "event": "failed", "output": path, "error": e.to_string() })
+                );
+                code = if e.is::<Cancelled>() {
+                    ExitCode::from(EXIT_CANCELLED)
+                } else {
+                    ExitCode::FAILURE
+                };
+            }
+        }
+    }
+    code
+}
+
+/// arguments of gui launch, `fast_clip video.mp4 --seek 120 --range 100-130`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LaunchArgs {
+    pub path: Option<PathBuf>,
+    pub seek: Option<f64>,
+    pub range: Option<Range<f64>>,
+}
+
+/// file managers pass every selected file, only the first one is opened
+pub fn parse_launch(args: &[String]) -> anyhow::Result<LaunchArgs> {
+    let mut launch = LaunchArgs::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .map(String::as_str)
+                .ok_or(anyhow!("missing value of {}", arg))
+        };
+        match arg.as_str() {
+            "--seek" => launch.seek = Some(parse_time(value()?)?),
+            "--range" => launch.range = Some(parse_range(value()?)?),
+            // process serial number added by older macOS launchers
+            other if other.starts_with("-psn_") => {}
+            other if other.starts_with('-') => bail!("unknown argument {}", other),
+            path => {
+                if launch.path.is_none() {
+                    launch.path = Some(PathBuf::from(path));
+                }
+            }
+        }
+    }
+    Ok(launch)
+}
+
+/// arguments of clip command before the source is probed
+#[derive(Debug, PartialEq)]
+struct ClipArgs {
+    input: PathBuf,
+    output: Option<PathBuf>,
+    start: Option<f64>,
+    end: Option<f64>,
+    ranges: Vec<Range<f64>>,
+    // audio track numbers, counted from 1
+    audio: Vec<usize>,
+    batch: Option<String>,
+    extra_streams: bool,
+    // follows output extension when not given
+    container: Option<Container>,
+    profile: ExportProfile,
+}
+
+fn parse_clip(args: &[String]) -> anyhow::Result<ClipArgs> {
+    let mut input = None;
+    let mut parsed = ClipArgs {
+        input: PathBuf::new(),
+        output: None,
+        start: None,
+        end: None,
+        ranges: vec![],
+        audio: vec![],
+        batch: None,
+        extra_streams: true,
+        container: None,
+        profile: ExportProfile::default(),
+    };
+    let profile = &mut parsed.profile;
+
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let mut value = || {
+            args.next()
+                .map(String::as_str)
+                .ok_or(anyhow!("missing value of {}", flag))
+        };
+        match flag.as_str() {
+            "-i" | "--input" => input = Some(PathBuf::from(value()?)),
+            "-o" | "--output" => parsed.output = Some(PathBuf::from(value()?)),
+            "--start" => parsed.start = Some(parse_time(value()?)?),
+            "--end" => parsed.end = Some(parse_time(value()?)?),
+            "--range" => parsed.ranges.push(parse_range(value()?)?),
+            "--audio" => {
+                let value = value()?;
+                match value.parse::<usize>() {
+                    Ok(n) if n > 0 => parsed.audio.push(n),
+                    _ => bail!("invalid audio track {}", value),
+                }
+            }
+            "--container" => {
+                let value = value()?;
+                parsed.container = Some(
+                    Container::from_value(value).ok_or(anyhow!("unknown container {}", value))?,
+                );
+            }
+            "--transcode" => profile.copy_stream = false,
+            "--video-codec" => {
+                let value = value()?;
+                profile.video_codec = VideoCodec::from_value(value)
+                    .ok_or(anyhow!("unknown video codec {}", value))?;
+            }
+            "--audio-codec" => {
+                let value = value()?;
+                profile.audio_codec = AudioCodec::from_value(value)
+                    .ok_or(anyhow!("unknown audio codec {}", value))?;
+            }
+            "--crf" => {
+                profile.crf = parse_number(value()?)?;
+                profile.rate_control = RateControl::Crf;
+            }
+            "--video-bitrate" => {
+                profile.video_bitrate = parse_number(value()?)?;
+                profile.rate_control = RateControl::Bitrate;
+            }
+            "--audio-bitrate" => profile.audio_bitrate = parse_number(value()?)?,
+            "--frame-accurate" => profile.frame_accurate = true,
+            "--batch" => parsed.batch = Some(value()?.to_string()),
+            "--no-extra-streams" => parsed.extra_streams = false,
+            "--strip-metadata" => profile.strip_metadata = true,
+            other => bail!("unknown argument {}", other),
+        }
+    }
+    profile.crf = profile.crf.min(profile.video_codec.max_crf());
+    parsed.input = input.ok_or(anyhow!("--input is required"))?;
+    Ok(parsed)
+}
+
+/// probe the source and resolve ranges and track numbers to stream indexes
+fn build_job(args: ClipArgs) -> anyhow::Result<OutputJob> {
+    let input = ffmpeg_next::format::input(&args.input)?;
+    // none for audio only files
+    let video_ix = input.streams().best(media::Type::Video).map(|s| s.index());
+    let audio_streams: Vec<usize> = input
+        .streams()
+        .filter(|s| s.parameters().medium() == media::Type::Audio)
+        .map(|s| s.index())
+        .collect();
+    let audio_ixs = if args.audio.is_empty() {
+        // video only when the file has no audio
+        input
+            .streams()
+            .best(media::Type::Audio)
+            .map(|s| s.index())
+            .into_iter()
+            .collect()
+    } else {
+        args.audio
+            .iter()
+            .map(|n| {
+                audio_streams
+                    .get(n - 1)
+                    .copied()
+                    .ok_or(anyhow!("audio track {} not found", n))
+            })
+            .collect::<anyhow::Result<Vec<usize>>>()?
+    };
+    let extra_ixs = if args.extra_streams {
+        input
+            .streams()
+            .filter(|s| StreamKind::from_medium(s.parameters().medium()).is_some())
+            .map(|s| s.index())
+            .collect()
+    } else {
+        vec![]
+    };
+
+    let mut ranges = args.ranges.clone();
+    if args.start.is_some() || args.end.is_some() || ranges.is_empty() {
+        let end = match args.end {
+            Some(end) => end,
+            None => input_duration(&input)
+                .ok_or(anyhow!("length of input is unknown, set --end or --range"))?,
+        };
+        let range = args.start.unwrap_or(0.)..end;
+        if range.end <= range.start {
+            bail!("end of range is before its start");
+        }
+        ranges.insert(0, range);
+    }
+
+    let out_path = args
+        .output
+        .clone()
+        .unwrap_or_else(|| default_output(&args.input));
+    let mut profile = args.profile.clone();
+    profile.container = args
+        .container
+        .or_else(|| {
+            out_path
+                .extension()
+                .and_then(|ext| Container::from_extension(&ext.to_string_lossy()))
+        })
+        .unwrap_or(profile.container);
+    let out_path = out_path.with_extension(profile.container.extension());
+
+    Ok(OutputJob {
+        path: args.input.clone(),
+        out_path,
+        video_ix,
+        audio_ixs,
+        extra_ixs,
+        batch_template: args.batch.clone().filter(|_| ranges.len() > 1),
+        ranges,
+        profile,
+        audio_gains: None,
+        markers: vec![],
+    })
+}
+
+/// `<input>_edit` next to input, keeping its extension so the container follows it
+fn default_output(input: &Path) -> PathBuf {
+    let stem = input
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or("output".into());
+    let name = match input.extension() {
+        Some(ext) => format!("{}_edit.{}", stem, ext.to_string_lossy()),
+        None => format!("{}_edit", stem),
+    };
+    input.with_file_name(name)
+}
+
+/// length of input in seconds, from the longest stream when the container does not tell
+fn input_duration(input: &format::context::Input) -> Option<f64> {
+    if input.duration() > 0 {
+        return Some(input.duration() as f64 / ffmpeg_next::sys::AV_TIME_BASE as f64);
+    }
+    input
+        .streams()
+        .filter(|s| s.duration() > 0)
+        .map(|s| s.duration() as f64 * f64::from(s.time_base()))
+        .reduce(f64::max)
+}
+
+/// seconds, `mm:ss` or `hh:mm:ss`, each with optional fraction
+fn parse_time(value: &str) -> anyhow::Result<f64> {
+    let parts: Vec<&str> = value.trim().split(':').collect();
+    if parts.len() > 3 {
+        bail!("invalid time {}", value);
+    }
+    let mut sec = 0.;
+    for part in parts {
+        let n = part
+            .parse::<f64>()
+            .ok()
+            .filter(|n| *n >= 0.)
+            .ok_or(anyhow!("invalid time {}", value))?;
+        sec = sec * 60. + n;
+    }
+    Ok(sec)
+}
+
+fn parse_range(value: &str) -> anyhow::Result<Range<f64>> {
+    let (start, end) = value
+        .split_once('-')
+        .ok_or(anyhow!("invalid range {}, expected start-end", value))?;
+    let range = parse_time(start)?..parse_time(end)?;
+    if range.end <= range.start {
+        bail!("end of range {} is before its start", value);
+    }
+    Ok(range)
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> anyhow::Result<T> {
+    value
+        .parse::<T>()
+        .map_err(|_| anyhow!("invalid number {}", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_time_accepts_seconds_and_clock() {
+        assert_eq!(parse_time("62.5").unwrap(), 62.5);
+        assert_eq!(parse_time("01:02.5").unwrap(), 62.5);
+        assert_eq!(parse_time("00:01:02.5").unwrap(), 62.5);
+        assert!(parse_time("1:2:3:4").is_err());
+        assert!(parse_time("-3").is_err());
+    }
+
+    #[test]
+    fn parse_launch_keeps_first_path() {
+        let args: Vec<String> = ["a.mp4", "--seek", "2:00", "b.mp4", "--range", "100-130"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        assert_eq!(
+            parse_launch(&args).unwrap(),
+            LaunchArgs {
+                path: Some(PathBuf::from("a.mp4")),
+                seek: Some(120.),
+                range: Some(100.0..130.0),
+            }
+        );
+        assert!(parse_launch(&["--volume".to_string()]).is_err());
+    }
+
+    #[test]
+    fn parse_clip_reads_ranges_and_tracks() {
+        let args: Vec<String> = [
+            "-i",
+            "a.mkv",
+            "--range",
+            "10-20",
+            "--range",
+            "1:00-1:30",
+            "--audio",
+            "2",
+            "--transcode",
+            "-o",
+            "out.mp4",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        let parsed = parse_clip(&args).unwrap();
+
+        assert_eq!(parsed.input, PathBuf::from("a.mkv"));
+        assert_eq!(parsed.ranges, vec![10.0..20.0, 60.0..90.0]);
+        assert_eq!(parsed.audio, vec![2]);
+        assert!(!parsed.profile.copy_stream);
+        assert!(parse_clip(&["--audio".to_string(), "0".to_string()]).is_err());
+    }
+
+    #[test]
+    fn default_output_keeps_input_extension() {
+        assert_eq!(
+            default_output(Path::new("/v/a.b.mkv")),
+            PathBuf::from("/v/a.b_edit.mkv")
+        );
+        assert_eq!(
+            default_output(Path::new("clip")),
+            PathBuf::from("clip_edit")
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use gpui::{Action, App, BorrowAppContext, Entity, Menu, MenuItem, SharedString, actions};
 use gpui_component::{GlobalState, Theme, menu::AppMenuBar};
 use rust_i18n::t;
@@ -13,8 +15,20 @@ actions!(
         About,
         Open,
         Close,
+        OpenProject,
+        SaveProject,
+        ClearRecentFiles,
         Output,
-        ClearSelectedRange
+        ClearSelectedRange,
+        AddRange,
+        RemoveRange,
+        AddMarker,
+        PrevMarker,
+        NextMarker,
+        Markers,
+        AudioMixer,
+        StepBack,
+        StepForward
     ]
 );
 
@@ -26,6 +40,10 @@ pub struct SelectLocale(pub SharedString);
 #[action(namespace = menu, no_json)]
 pub struct SelectAudioRail(pub usize);
 
+#[derive(Action, Clone, PartialEq, Eq)]
+#[action(namespace = menu, no_json)]
+pub struct OpenRecent(pub PathBuf);
+
 pub fn init(
     cx: &mut App,
     title: impl Into<SharedString>,
@@ -66,14 +84,34 @@ pub fn init(
         move |s: &SelectAudioRail, cx: &mut App| {
             let new_ix = s.0;
             p_settings.update(cx, |s, cx| {
-                if s.audio_ix != new_ix {
+                // picking one rail leaves the mix
+                if s.audio_ix != new_ix || !s.mix.is_empty() {
                     s.audio_ix = new_ix;
+                    s.mix.clear();
                     cx.notify();
                 }
             });
         }
     });
 
+    cx.on_action(|_: &ClearRecentFiles, cx: &mut App| {
+        cx.update_global(|g: &mut AppConfig, _| {
+            g.recent_files.clear();
+            g.save();
+        });
+    });
+
+    // recent files are kept in config
+    cx.observe_global::<AppConfig>({
+        let title = title.clone();
+        let app_menu_bar = app_menu_bar.clone();
+        let p_settings = player_settings.clone();
+        move |cx| {
+            update_app_menu(title.clone(), app_menu_bar.clone(), cx, p_settings.clone());
+        }
+    })
+    .detach();
+
     // Observe theme changes to update the menu to refresh the checked state
     cx.observe_global::<Theme>({
         let title = title.clone();
@@ -116,23 +154,40 @@ fn build_menus(
             disabled: false,
             items: vec![
                 MenuItem::action(t!("menu.file.open"), Open),
+                recent_files_menu(cx),
                 MenuItem::action(t!("menu.file.close"), Close),
                 MenuItem::Separator,
+                MenuItem::action(t!("menu.file.open_project"), OpenProject),
+                MenuItem::action(t!("menu.file.save_project"), SaveProject),
+                MenuItem::Separator,
                 MenuItem::action(t!("menu.file.export"), Output),
             ],
         },
         Menu {
             name: SharedString::from(t!("menu.player.title")),
             disabled: false,
-            items: vec![audio_rails_menu(cx, player_settings)],
+            items: vec![
+                audio_rails_menu(cx, player_settings),
+                MenuItem::action(t!("menu.player.audio_mixer"), AudioMixer),
+                MenuItem::Separator,
+                MenuItem::action(t!("menu.player.step_back"), StepBack),
+                MenuItem::action(t!("menu.player.step_forward"), StepForward),
+            ],
         },
         Menu {
             name: SharedString::from(t!("menu.editor.title")),
             disabled: false,
-            items: vec![MenuItem::action(
-                t!("menu.editor.clear_selected_range"),
-                ClearSelectedRange,
-            )],
+            items: vec![
+                MenuItem::action(t!("menu.editor.clear_selected_range"), ClearSelectedRange),
+                MenuItem::Separator,
+                MenuItem::action(t!("menu.editor.add_range"), AddRange),
+                MenuItem::action(t!("menu.editor.remove_range"), RemoveRange),
+                MenuItem::Separator,
+                MenuItem::action(t!("menu.editor.add_marker"), AddMarker),
+                MenuItem::action(t!("menu.editor.prev_marker"), PrevMarker),
+                MenuItem::action(t!("menu.editor.next_marker"), NextMarker),
+                MenuItem::action(t!("menu.editor.markers"), Markers),
+            ],
         },
     ]
 }
@@ -173,6 +228,35 @@ fn language_menu() -> MenuItem {
     })
 }
 
+fn recent_files_menu(cx: &App) -> MenuItem {
+    let recent_files = &cx.global::<AppConfig>().recent_files;
+    let mut items: Vec<MenuItem> = recent_files
+        .iter()
+        .map(|f| {
+            let name = f
+                .path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| f.path.to_string_lossy().into_owned());
+            MenuItem::action(name, OpenRecent(f.path.clone()))
+        })
+        .collect();
+    let length = items.len();
+    if length > 0 {
+        items.push(MenuItem::Separator);
+        items.push(MenuItem::action(
+            t!("menu.file.clear_recent"),
+            ClearRecentFiles,
+        ));
+    }
+
+    MenuItem::submenu(Menu {
+        name: SharedString::from(t!("menu.file.recent")),
+        items,
+        disabled: length == 0,
+    })
+}
+
 fn audio_rails_menu(cx: &App, player_settings: Entity<PlayerSettings>) -> MenuItem {
     let settings = player_settings.read(cx);
     let mut items = vec![];
@@ -187,7 +271,11 @@ fn audio_rails_menu(cx: &App, player_settings: Entity<PlayerSettings>) -> MenuIt
             ),
             SelectAudioRail(s.ix),
         )
-        .checked(settings.audio_ix == s.ix);
+        .checked(if settings.mix.is_empty() {
+            settings.audio_ix == s.ix
+        } else {
+            settings.mix.iter().any(|m| m.ix == s.ix)
+        });
 
         items.push(item);
     }
@@ -1,4 +1,4 @@
-use std::{env, fs, path::PathBuf};
+use std::{env, fs, ops::Range, path::PathBuf};
 
 use gpui::Global;
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
@@ -64,6 +64,217 @@ impl StepMode {
     }
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, EnumIter)]
+#[serde(rename_all = "snake_case")]
+pub enum VideoCodec {
+    H264,
+    H265,
+    Vp9,
+    Av1,
+}
+
+impl VideoCodec {
+    pub fn from_value(value: &str) -> Option<Self> {
+        serde_from_string(value)
+    }
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::H264 => "H.264",
+            Self::H265 => "H.265 (HEVC)",
+            Self::Vp9 => "VP9",
+            Self::Av1 => "AV1",
+        }
+    }
+
+    /// software encoders in order of preference
+    pub const fn encoder_names(self) -> &'static [&'static str] {
+        match self {
+            Self::H264 => &["libx264"],
+            Self::H265 => &["libx265"],
+            Self::Vp9 => &["libvpx-vp9"],
+            Self::Av1 => &["libsvtav1", "libaom-av1", "librav1e"],
+        }
+    }
+
+    pub const fn max_crf(self) -> u8 {
+        match self {
+            Self::H264 | Self::H265 => 51,
+            Self::Vp9 | Self::Av1 => 63,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, EnumIter)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioCodec {
+    Aac,
+    Opus,
+}
+
+impl AudioCodec {
+    pub fn from_value(value: &str) -> Option<Self> {
+        serde_from_string(value)
+    }
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Aac => "AAC",
+            Self::Opus => "Opus",
+        }
+    }
+
+    /// encoders in order of preference
+    pub const fn encoder_names(self) -> &'static [&'static str] {
+        match self {
+            Self::Aac => &["aac"],
+            Self::Opus => &["libopus", "opus"],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, EnumIter)]
+#[serde(rename_all = "snake_case")]
+pub enum Container {
+    Mp4,
+    Mkv,
+    Mov,
+    Webm,
+    Ts,
+}
+
+impl Container {
+    pub fn from_value(value: &str) -> Option<Self> {
+        serde_from_string(value)
+    }
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Mp4 => "MP4",
+            Self::Mkv => "MKV",
+            Self::Mov => "MOV",
+            Self::Webm => "WebM",
+            Self::Ts => "MPEG-TS",
+        }
+    }
+
+    /// short name of the ffmpeg muxer
+    pub const fn muxer(self) -> &'static str {
+        match self {
+            Self::Mp4 => "mp4",
+            Self::Mkv => "matroska",
+            Self::Mov => "mov",
+            Self::Webm => "webm",
+            Self::Ts => "mpegts",
+        }
+    }
+
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Mp4 => "mp4",
+            Self::Mkv => "mkv",
+            Self::Mov => "mov",
+            Self::Webm => "webm",
+            Self::Ts => "ts",
+        }
+    }
+
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext.to_ascii_lowercase().as_str() {
+            "mp4" | "m4v" => Some(Self::Mp4),
+            "mkv" => Some(Self::Mkv),
+            "mov" => Some(Self::Mov),
+            "webm" => Some(Self::Webm),
+            "ts" | "m2ts" => Some(Self::Ts),
+            _ => None,
+        }
+    }
+
+    /// options given to the muxer when writing header,
+    /// faststart moves the index to the front so playback starts before download ends
+    pub const fn muxer_options(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::Mp4 | Self::Mov => &[("movflags", "+faststart")],
+            Self::Mkv | Self::Webm | Self::Ts => &[],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, EnumIter)]
+#[serde(rename_all = "snake_case")]
+pub enum RateControl {
+    Crf,
+    Bitrate,
+}
+
+impl RateControl {
+    pub const fn i18n_key(self) -> &'static str {
+        match self {
+            Self::Crf => "output.rate_control.crf",
+            Self::Bitrate => "output.rate_control.bitrate",
+        }
+    }
+}
+
+/// last used export settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "snake_case")]
+pub struct ExportProfile {
+    pub container: Container,
+    pub copy_stream: bool,
+    pub frame_accurate: bool,
+    pub video_codec: VideoCodec,
+    pub audio_codec: AudioCodec,
+    pub rate_control: RateControl,
+    pub crf: u8,
+    /// kbps
+    pub video_bitrate: u32,
+    /// kbps
+    pub audio_bitrate: u32,
+    pub keep_subtitles: bool,
+    pub keep_attachments: bool,
+    pub keep_data: bool,
+    /// drop tags such as creation time and location
+    pub strip_metadata: bool,
+    /// write markers as chapters instead of chapters of source
+    pub markers_as_chapters: bool,
+}
+
+impl Default for ExportProfile {
+    fn default() -> Self {
+        Self {
+            container: Container::Mp4,
+            copy_stream: true,
+            frame_accurate: false,
+            video_codec: VideoCodec::H264,
+            audio_codec: AudioCodec::Aac,
+            rate_control: RateControl::Crf,
+            crf: 23,
+            video_bitrate: 8000,
+            audio_bitrate: 192,
+            keep_subtitles: true,
+            keep_attachments: true,
+            keep_data: true,
+            strip_metadata: false,
+            markers_as_chapters: true,
+        }
+    }
+}
+
+/// length of recent files list
+static MAX_RECENT_FILES: usize = 10;
+
+/// opened file and where editing stopped in it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct RecentFile {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub position: f64,
+    #[serde(default)]
+    pub selection: Option<Range<f64>>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default, rename_all = "snake_case")]
 pub struct AppConfig {
@@ -73,6 +284,9 @@ pub struct AppConfig {
     pub step_mode: StepMode,
     pub step_percent: f64,
     pub step_sec: f64,
+    pub export_profile: ExportProfile,
+    // latest first
+    pub recent_files: Vec<RecentFile>,
 }
 
 impl Default for AppConfig {
@@ -84,6 +298,8 @@ impl Default for AppConfig {
             step_mode: StepMode::Percent,
             step_percent: 0.01,
             step_sec: 5.0,
+            export_profile: ExportProfile::default(),
+            recent_files: vec![],
         }
     }
 }
@@ -98,6 +314,17 @@ impl AppConfig {
         if forward { now + step } else { now - step }
     }
 
+    pub fn recent_file(&self, path: &PathBuf) -> Option<&RecentFile> {
+        self.recent_files.iter().find(|f| &f.path == path)
+    }
+
+    /// put file on top of recent files, replacing its old entry
+    pub fn remember_file(&mut self, file: RecentFile) {
+        self.recent_files.retain(|f| f.path != file.path);
+        self.recent_files.insert(0, file);
+        self.recent_files.truncate(MAX_RECENT_FILES);
+    }
+
     pub fn save(&self) -> Option<anyhow::Error> {
         if let Err(e) = save(self) {
             println!("failed to save config: {}", e);
@@ -132,6 +359,11 @@ pub fn save(config: &AppConfig) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// derived data such as thumbnails, safe to delete
+pub fn cache_dir() -> anyhow::Result<PathBuf> {
+    Ok(config_dir()?.join("cache"))
+}
+
 fn config_path() -> anyhow::Result<PathBuf> {
     Ok(config_dir()?.join("config.json"))
 }
@@ -208,4 +440,47 @@ mod tests {
         assert_eq!(config.handle_seek(20.0, 200.0, true), 27.5);
         assert_eq!(config.handle_seek(20.0, 200.0, false), 12.5);
     }
+
+    #[test]
+    fn config_without_export_profile_loads_default_profile() {
+        let config: AppConfig = serde_json::from_str(r#"{"step_sec": 3.0}"#).unwrap();
+
+        assert_eq!(config.step_sec, 3.0);
+        assert_eq!(config.export_profile, ExportProfile::default());
+    }
+
+    #[test]
+    fn remember_file_moves_entry_to_top() {
+        let mut config = AppConfig::default();
+        for i in 0..=MAX_RECENT_FILES {
+            config.remember_file(RecentFile {
+                path: PathBuf::from(format!("{}.mp4", i)),
+                position: 0.,
+                selection: None,
+            });
+        }
+        config.remember_file(RecentFile {
+            path: PathBuf::from("5.mp4"),
+            position: 42.,
+            selection: None,
+        });
+
+        assert_eq!(config.recent_files.len(), MAX_RECENT_FILES);
+        assert_eq!(config.recent_files[0].position, 42.);
+        assert_eq!(
+            config
+                .recent_files
+                .iter()
+                .filter(|f| f.path == PathBuf::from("5.mp4"))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn container_from_extension_ignores_case() {
+        assert_eq!(Container::from_extension("MKV"), Some(Container::Mkv));
+        assert_eq!(Container::from_extension("m4v"), Some(Container::Mp4));
+        assert_eq!(Container::from_extension("avi"), None);
+    }
 }
@@ -0,0 +1,140 @@
+use async_channel::Receiver;
+
+use crate::cli::LaunchArgs;
+
+/// hand launch arguments to a running instance, true when one took them
+#[cfg(target_os = "linux")]
+pub fn forward(launch: &LaunchArgs) -> bool {
+    use std::{io::Write, os::unix::net::UnixStream};
+
+    let Some(path) = socket_path() else {
+        return false;
+    };
+    let Ok(mut stream) = UnixStream::connect(path) else {
+        return false;
+    };
+    // the running instance has another working directory
+    let mut launch = launch.clone();
+    launch.path = launch.path.map(|p| p.canonicalize().unwrap_or(p));
+    let Ok(message) = serde_json::to_vec(&launch) else {
+        return false;
+    };
+    match stream.write_all(&message) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("failed to forward to running instance: {e}");
+            false
+        }
+    }
+}
+
+/// become the instance later launches forward to, each request is one launch.
+/// when another instance started meanwhile, launch is handed to it and the process exits
+#[cfg(target_os = "linux")]
+pub fn listen(launch: &LaunchArgs) -> Option<Receiver<LaunchArgs>> {
+    use std::{
+        fs,
+        io::{ErrorKind, Read},
+        os::unix::net::{UnixListener, UnixStream},
+        process, thread,
+    };
+
+    let Some(path) = socket_path() else {
+        eprintln!("failed to listen: no runtime dir or user id for socket");
+        return None;
+    };
+    let listener = match UnixListener::bind(&path) {
+        Err(e) if e.kind() == ErrorKind::AddrInUse => {
+            // another instance started meanwhile owns it, keep its socket
+            if UnixStream::connect(&path).is_ok() {
+                if forward(launch) {
+                    process::exit(0);
+                }
+                return None;
+            }
+            // nobody answers, the socket is left by a crashed instance
+            let _ = fs::remove_file(&path);
+            UnixListener::bind(&path)
+        }
+        result => result,
+    };
+    let listener = match listener {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("failed to listen on {:?}: {e}", path);
+            return None;
+        }
+    };
+
+    let (tx, rx) = async_channel::unbounded();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut message = String::new();
+            if let Err(e) = stream.and_then(|mut s| s.read_to_string(&mut message)) {
+                eprintln!("failed to read launch request: {e}");
+                continue;
+            }
+            match serde_json::from_str::<LaunchArgs>(&message) {
+                Ok(launch) => {
+                    if tx.send_blocking(launch).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => eprintln!("invalid launch request: {e}"),
+            }
+        }
+    });
+    Some(rx)
+}
+
+/// remove the socket of this instance on exit, only when `listen` returned it
+#[cfg(target_os = "linux")]
+pub fn release() {
+    if let Some(path) = socket_path() {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// socket of this user, in a private dir of temp dir without runtime dir.
+/// none when the user id is unknown or the dir is open to other users
+#[cfg(target_os = "linux")]
+fn socket_path() -> Option<std::path::PathBuf> {
+    use std::{
+        env, fs,
+        io::ErrorKind,
+        os::unix::fs::{DirBuilderExt, MetadataExt},
+    };
+
+    if let Some(dir) = env::var_os("XDG_RUNTIME_DIR") {
+        return Some(std::path::PathBuf::from(dir).join("fast_clip.sock"));
+    }
+    // owner of /proc/self is the uid of this process
+    let uid = fs::metadata("/proc/self").ok()?.uid();
+    let dir = env::temp_dir().join(format!("fast_clip-{}", uid));
+    if let Err(e) = fs::DirBuilder::new().mode(0o700).create(&dir)
+        && e.kind() != ErrorKind::AlreadyExists
+    {
+        eprintln!("failed to create socket dir {:?}: {e}", dir);
+        return None;
+    }
+    // anyone else able to write there could take the socket over
+    let meta = fs::symlink_metadata(&dir).ok()?;
+    if !meta.is_dir() || meta.uid() != uid || meta.mode() & 0o077 != 0 {
+        eprintln!("socket dir {:?} is not private to this user", dir);
+        return None;
+    }
+    Some(dir.join("fast_clip.sock"))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn forward(_: &LaunchArgs) -> bool {
+    false
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn listen(_: &LaunchArgs) -> Option<Receiver<LaunchArgs>> {
+    None
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn release() {}
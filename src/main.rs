@@ -1,12 +1,15 @@
 #![cfg_attr(all(windows, not(debug_assertions)), windows_subsystem = "windows")]
-use std::{env, rc::Rc, sync::Arc};
+use std::{env, process::ExitCode, rc::Rc, sync::Arc};
 
 use gpui::*;
 use gpui_component::*;
 use rust_i18n::t;
 
 use crate::{
-    components::app_menu::{About, Open, Output, Quit, Settings},
+    components::app_menu::{
+        About, AddMarker, AddRange, NextMarker, Open, OpenRecent, Output, PrevMarker, Quit,
+        RemoveRange, Settings, StepBack, StepForward,
+    },
     config::AppConfig,
     models::model::{OutputParams, WindowState},
     ui::{
@@ -15,8 +18,10 @@ use crate::{
     },
 };
 use reqwest_client;
+mod cli;
 mod components;
 mod config;
+mod instance;
 mod models;
 mod ui;
 mod update;
@@ -24,7 +29,18 @@ mod update;
 rust_i18n::i18n!("locales", fallback = "en");
 
 actions!([
-    Back, Forward, SwitchPlay, ToRangeA, ToRangeB, SetStart, SetEnd, VolumeUp, VolumeDown
+    Back,
+    Forward,
+    SwitchPlay,
+    ToRangeA,
+    ToRangeB,
+    SetStart,
+    SetEnd,
+    VolumeUp,
+    VolumeDown,
+    ShuttleBack,
+    ShuttleStop,
+    ShuttleForward
 ]);
 
 #[cfg(target_os = "macos")]
@@ -32,9 +48,26 @@ static OUTPUT_KEY: &str = "cmd-s";
 #[cfg(not(target_os = "macos"))]
 static OUTPUT_KEY: &str = "ctrl-s";
 
-fn main() {
+fn main() -> ExitCode {
     ffmpeg_next::init().unwrap();
 
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Some(code) = cli::run(&args) {
+        return code;
+    }
+    let launch = match cli::parse_launch(&args) {
+        Ok(launch) => launch,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::from(2);
+        }
+    };
+    if instance::forward(&launch) {
+        return ExitCode::SUCCESS;
+    }
+    let launch_requests = instance::listen(&launch);
+    let owns_socket = launch_requests.is_some();
+
     let http = reqwest_client::ReqwestClient::user_agent(
         format!("Fastclip/{}", env!("CARGO_PKG_VERSION")).as_str(),
     )
@@ -54,7 +87,11 @@ fn main() {
         // let config_entity: Entity<AppConfig> = cx.new(|_| config);
 
         let size_entity = cx.new(|_cx| PlayerSize::new());
-        let params_entity: Entity<OutputParams> = cx.new(|_| OutputParams::default());
+        let export_profile = cx.global::<AppConfig>().export_profile.clone();
+        let params_entity: Entity<OutputParams> = cx.new(|_| OutputParams {
+            export_profile,
+            ..OutputParams::default()
+        });
         let window_state = cx.new(|_| WindowState::default());
 
         cx.set_http_client(Arc::new(http));
@@ -82,6 +119,10 @@ fn main() {
                         .detach();
                         MyApp::new(cx, size_entity, params_entity.clone())
                     });
+                    view.update(cx, |app, cx| app.open_launch(cx, &launch));
+                    if let Some(requests) = launch_requests {
+                        listen_launch(requests, view.clone(), window.window_handle(), cx);
+                    }
                     cx.new(|cx| Root::new(view, window, cx))
                 },
             )
@@ -91,12 +132,36 @@ fn main() {
         cx.on_action(|_: &Quit, cx| {
             cx.quit();
         });
+        if owns_socket {
+            cx.on_app_quit(|_| {
+                instance::release();
+                async {}
+            })
+            .detach();
+        }
         cx.on_action(open_settings_window(window_state.clone()));
         cx.on_action(open_about_dialog(app_window.clone()));
         cx.on_action(open_output_dialog(
             app_window.clone(),
             params_entity.clone(),
         ));
+        cx.on_action({
+            let params = params_entity.clone();
+            move |a: &OpenRecent, cx| {
+                if !a.0.is_file() {
+                    eprintln!("failed to open {:?}: not a file", a.0);
+                    cx.update_global(|g: &mut AppConfig, _| {
+                        g.recent_files.retain(|f| f.path != a.0);
+                        g.save();
+                    });
+                    return;
+                }
+                params.update(cx, |p, cx| {
+                    p.path = Some(a.0.clone());
+                    cx.notify();
+                });
+            }
+        });
         cx.on_action(move |_: &Open, cx| {
             let result = cx.prompt_for_paths(gpui::PathPromptOptions {
                 files: true,
@@ -138,14 +203,25 @@ fn main() {
             .detach();
         }
     });
+    ExitCode::SUCCESS
 }
 
 fn bind_keys(cx: &mut App) {
     cx.bind_keys([KeyBinding::new("space", SwitchPlay, None)]);
     cx.bind_keys([KeyBinding::new("left", Back, None)]);
     cx.bind_keys([KeyBinding::new("right", Forward, None)]);
+    cx.bind_keys([KeyBinding::new(",", StepBack, None)]);
+    cx.bind_keys([KeyBinding::new(".", StepForward, None)]);
+    cx.bind_keys([KeyBinding::new("j", ShuttleBack, None)]);
+    cx.bind_keys([KeyBinding::new("k", ShuttleStop, None)]);
+    cx.bind_keys([KeyBinding::new("l", ShuttleForward, None)]);
     cx.bind_keys([KeyBinding::new("[", SetStart, None)]);
     cx.bind_keys([KeyBinding::new("]", SetEnd, None)]);
+    cx.bind_keys([KeyBinding::new("=", AddRange, None)]);
+    cx.bind_keys([KeyBinding::new("-", RemoveRange, None)]);
+    cx.bind_keys([KeyBinding::new("m", AddMarker, None)]);
+    cx.bind_keys([KeyBinding::new("shift-left", PrevMarker, None)]);
+    cx.bind_keys([KeyBinding::new("shift-right", NextMarker, None)]);
     cx.bind_keys([KeyBinding::new("up", VolumeUp, None)]);
     cx.bind_keys([KeyBinding::new("down", VolumeDown, None)]);
     cx.bind_keys([KeyBinding::new(OUTPUT_KEY, Output, None)]);
@@ -212,6 +288,22 @@ fn open_output_dialog(
     }
 }
 
+/// open files forwarded by later launches and raise the window
+fn listen_launch(
+    requests: async_channel::Receiver<cli::LaunchArgs>,
+    view: Entity<MyApp>,
+    window: AnyWindowHandle,
+    cx: &mut App,
+) {
+    cx.spawn(async move |cx| {
+        while let Ok(launch) = requests.recv().await {
+            let _ = view.update(cx, |app, cx| app.open_launch(cx, &launch));
+            let _ = cx.update_window(window, |_, w, _| w.activate_window());
+        }
+    })
+    .detach();
+}
+
 fn open_about_dialog(window: AnyWindowHandle) -> impl Fn(&About, &mut App) {
     move |_: &About, cx: &mut App| {
         cx.defer(move |cx| {
@@ -2,8 +2,12 @@ use std::{ops::Range, path::PathBuf};
 
 use gpui::{App, WindowHandle};
 use gpui_component::Root;
+use serde::{Deserialize, Serialize};
 
-use crate::ui::player::model::AudioRail;
+use crate::{
+    config::ExportProfile,
+    ui::player::model::{AudioRail, ExtraStream, MixTrack},
+};
 
 pub struct WindowState {
     pub settings_handle: Option<WindowHandle<Root>>,
@@ -26,13 +30,55 @@ impl WindowState {
     }
 }
 
+/// named moment on the timeline, in seconds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Marker {
+    pub name: String,
+    pub time: f64,
+}
+
+/// range listed for export, in seconds, with a label given by the user
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClipRange {
+    pub start: f64,
+    pub end: f64,
+    // empty when not named, missing in projects saved before labels
+    #[serde(default)]
+    pub label: String,
+}
+
+impl ClipRange {
+    pub fn new(range: Range<f64>) -> Self {
+        Self {
+            start: range.start,
+            end: range.end,
+            label: String::new(),
+        }
+    }
+
+    pub fn range(&self) -> Range<f64> {
+        self.start..self.end
+    }
+}
+
+/// jumping back right after passing a marker goes to the one before it
+static MARKER_GRACE_SEC: f64 = 0.5;
+
 #[derive(Debug)]
 pub struct OutputParams {
     pub path: Option<PathBuf>,
     pub video_stream_ix: Option<usize>,
     pub audio_stream_ix: Option<usize>,
     pub selected_range: Option<Range<f64>>,
+    pub ranges: Vec<ClipRange>,
     pub audio_rails: Option<Vec<AudioRail>>,
+    // tracks mixed in player, used as default of export mix
+    pub audio_mix: Vec<MixTrack>,
+    // subtitle, attachment and data streams of source
+    pub extra_streams: Vec<ExtraStream>,
+    // sorted by time
+    pub markers: Vec<Marker>,
+    pub export_profile: ExportProfile,
 }
 
 impl OutputParams {
@@ -42,15 +88,90 @@ impl OutputParams {
             video_stream_ix: None,
             audio_stream_ix: None,
             selected_range: None,
+            ranges: Vec::new(),
             audio_rails: None,
+            audio_mix: Vec::new(),
+            extra_streams: Vec::new(),
+            markers: Vec::new(),
+            export_profile: ExportProfile::default(),
         }
     }
 
     pub fn all_some(&self) -> bool {
         self.path.is_some()
-            && self.video_stream_ix.is_some()
-            && self.audio_stream_ix.is_some()
-            && self.selected_range.is_some()
+            && (self.video_stream_ix.is_some() || self.audio_stream_ix.is_some())
+            && !self.export_ranges().is_empty()
             && self.audio_rails.is_some()
     }
+
+    /// insert marker keeping time order
+    pub fn add_marker(&mut self, marker: Marker) {
+        let ix = self.markers.partition_point(|m| m.time <= marker.time);
+        self.markers.insert(ix, marker);
+    }
+
+    pub fn next_marker(&self, now: f64) -> Option<&Marker> {
+        self.markers.iter().find(|m| m.time > now + 0.01)
+    }
+
+    pub fn prev_marker(&self, now: f64) -> Option<&Marker> {
+        self.markers
+            .iter()
+            .rev()
+            .find(|m| m.time < now - MARKER_GRACE_SEC)
+    }
+
+    /// ranges to export, the marked list or the current selection
+    pub fn export_ranges(&self) -> Vec<Range<f64>> {
+        if self.ranges.is_empty() {
+            self.selected_range.clone().into_iter().collect()
+        } else {
+            self.ranges.iter().map(ClipRange::range).collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marker(time: f64) -> Marker {
+        Marker {
+            name: String::new(),
+            time,
+        }
+    }
+
+    #[test]
+    fn markers_stay_sorted_and_jump_around_playhead() {
+        let mut params = OutputParams::default();
+        for time in [30., 10., 20.] {
+            params.add_marker(marker(time));
+        }
+        let times: Vec<f64> = params.markers.iter().map(|m| m.time).collect();
+        assert_eq!(times, vec![10., 20., 30.]);
+
+        assert_eq!(params.next_marker(20.).map(|m| m.time), Some(30.));
+        assert_eq!(params.prev_marker(20.2).map(|m| m.time), Some(10.));
+        assert_eq!(params.prev_marker(21.).map(|m| m.time), Some(20.));
+        assert_eq!(params.next_marker(30.), None);
+    }
+
+    #[test]
+    fn exports_with_either_stream() {
+        let mut params = OutputParams::default();
+        params.path = Some(PathBuf::from("silent.mp4"));
+        params.video_stream_ix = Some(0);
+        params.audio_rails = Some(vec![]);
+        params.selected_range = Some(1.0..2.0);
+        assert!(params.all_some());
+
+        params.video_stream_ix = None;
+        assert!(!params.all_some());
+        params.audio_stream_ix = Some(0);
+        assert!(params.all_some());
+
+        params.selected_range = None;
+        assert!(!params.all_some());
+    }
 }
@@ -0,0 +1,131 @@
+use std::{fs, ops::Range, path::PathBuf};
+
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::ExportProfile,
+    models::model::{ClipRange, Marker},
+    ui::player::model::MixTrack,
+};
+
+pub static PROJECT_EXTENSION: &str = "fastclip";
+/// version written into projects, 2 added labels to ranges
+pub static PROJECT_VERSION: u32 = 2;
+
+/// editing session saved as `.fastclip` json
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "snake_case")]
+pub struct Project {
+    // missing in files which are no project
+    #[serde(default)]
+    pub version: u32,
+    pub source: PathBuf,
+    pub audio_ix: Option<usize>,
+    pub audio_mix: Vec<MixTrack>,
+    // A/B selection in seconds
+    pub selection: Option<Range<f64>>,
+    pub ranges: Vec<ClipRange>,
+    pub markers: Vec<Marker>,
+    pub export_profile: ExportProfile,
+    pub playhead: f64,
+}
+
+impl Default for Project {
+    fn default() -> Self {
+        Self {
+            version: PROJECT_VERSION,
+            source: PathBuf::new(),
+            audio_ix: None,
+            audio_mix: vec![],
+            selection: None,
+            ranges: vec![],
+            markers: vec![],
+            export_profile: ExportProfile::default(),
+            playhead: 0.,
+        }
+    }
+}
+
+impl Project {
+    /// read project of this or an older version, older ranges have no labels
+    pub fn load(path: &PathBuf) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        Self::parse(&content)
+    }
+
+    fn parse(content: &str) -> anyhow::Result<Self> {
+        let mut project: Self = serde_json::from_str(content)?;
+        if project.version == 0 || project.version > PROJECT_VERSION {
+            bail!("unsupported project version {}", project.version);
+        }
+        project.version = PROJECT_VERSION;
+        Ok(project)
+    }
+
+    pub fn save(&self, path: &PathBuf) -> anyhow::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// source file, or a file of same name next to the project when it was moved with it
+    pub fn locate_source(&self, project_path: &PathBuf) -> Option<PathBuf> {
+        if self.source.is_file() {
+            return Some(self.source.clone());
+        }
+        let moved = project_path.parent()?.join(self.source.file_name()?);
+        moved.is_file().then_some(moved)
+    }
+}
+
+/// project file suggested for a source, `clip.mp4` is saved as `clip.fastclip`
+pub fn project_path_for(source: &PathBuf) -> PathBuf {
+    source.with_extension(PROJECT_EXTENSION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locate_source_finds_file_moved_with_project() {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!(
+            "fast_clip_project_test-{}-{}",
+            std::process::id(),
+            nanos
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("clip.mp4");
+        fs::write(&source, b"").unwrap();
+
+        let project = Project {
+            source: PathBuf::from("/gone/clip.mp4"),
+            ..Project::default()
+        };
+        let project_path = project_path_for(&source);
+        assert_eq!(project.locate_source(&project_path), Some(source.clone()));
+        assert_eq!(
+            project.locate_source(&PathBuf::from("/gone/a.fastclip")),
+            None
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn parse_migrates_unlabeled_ranges_and_rejects_unknown_versions() {
+        let project = Project::parse(
+            r#"{"version": 1, "source": "clip.mp4", "ranges": [{"start": 1.0, "end": 2.5}]}"#,
+        )
+        .unwrap();
+        assert_eq!(project.version, PROJECT_VERSION);
+        assert_eq!(project.ranges, vec![ClipRange::new(1.0..2.5)]);
+
+        assert!(Project::parse(r#"{"version": 99, "source": "clip.mp4"}"#).is_err());
+        assert!(Project::parse(r#"{"source": "clip.mp4"}"#).is_err());
+    }
+}
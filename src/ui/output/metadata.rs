@@ -0,0 +1,206 @@
+use std::ops::Range;
+
+use ffmpeg_next::{
+    Dictionary, ffi,
+    format::{context, stream},
+};
+
+use crate::{models::model::Marker, ui::output::output::ts_to_sec};
+
+/// chapter placed on the exported timeline, in seconds
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChapterSpan {
+    pub title: String,
+    pub start: f64,
+    pub end: f64,
+}
+
+/// keep title, creation time and other tags of the source file
+pub fn copy_global_metadata(input: &context::Input, output: &mut context::Output, strip: bool) {
+    if !strip {
+        output.set_metadata(input.metadata().to_owned());
+    }
+}
+
+/// keep every tag of a source stream, only its name and language when stripped
+pub fn copy_stream_metadata(
+    stream: &stream::Stream,
+    out_stream: &mut stream::StreamMut,
+    strip: bool,
+) {
+    if strip {
+        copy_track_metadata(stream, out_stream);
+    } else {
+        out_stream.set_metadata(stream.metadata().to_owned());
+    }
+}
+
+/// keep the track name and language of a source stream
+pub fn copy_track_metadata(stream: &stream::Stream, out_stream: &mut stream::StreamMut) {
+    let source = stream.metadata();
+    let mut metadata = Dictionary::new();
+    for key in ["language", "handler_name"] {
+        if let Some(value) = source.get(key) {
+            metadata.set(key, value);
+        }
+    }
+    out_stream.set_metadata(metadata);
+}
+
+/// add side data of source stream the output does not have yet,
+/// parameters of an encoder carry no display matrix so rotated videos need this
+pub fn copy_side_data(stream: &stream::Stream, out_stream: &mut stream::StreamMut) {
+    for side_data in stream.side_data() {
+        let kind: ffi::AVPacketSideDataType = side_data.kind().into();
+        let data = side_data.data();
+        unsafe {
+            let par = (*out_stream.as_mut_ptr()).codecpar;
+            let existing = ffi::av_packet_side_data_get(
+                (*par).coded_side_data,
+                (*par).nb_coded_side_data,
+                kind,
+            );
+            if !existing.is_null() {
+                continue;
+            }
+            let added = ffi::av_packet_side_data_new(
+                &mut (*par).coded_side_data,
+                &mut (*par).nb_coded_side_data,
+                kind,
+                data.len(),
+                0,
+            );
+            if added.is_null() {
+                eprintln!("failed to copy side data {:?}", side_data.kind());
+                continue;
+            }
+            std::ptr::copy_nonoverlapping(data.as_ptr(), (*added).data, data.len());
+        }
+    }
+}
+
+/// chapters overlapping the range, cut to it and moved so the range starts at zero,
+/// marker chapters replace the chapters of source when given
+pub fn chapters_in(
+    input: &context::Input,
+    range: &Range<f64>,
+    markers: &[ChapterSpan],
+) -> Vec<ChapterSpan> {
+    let chapters = if markers.is_empty() {
+        source_chapters(input)
+    } else {
+        markers.to_vec()
+    };
+    chapters
+        .into_iter()
+        .filter_map(|chapter| {
+            let clipped = clip_chapter(&(chapter.start..chapter.end), range)?;
+            Some(ChapterSpan {
+                start: clipped.start,
+                end: clipped.end,
+                ..chapter
+            })
+        })
+        .collect()
+}
+
+fn source_chapters(input: &context::Input) -> Vec<ChapterSpan> {
+    input
+        .chapters()
+        .map(|chapter| {
+            let tb = chapter.time_base();
+            ChapterSpan {
+                title: chapter
+                    .metadata()
+                    .get("title")
+                    .unwrap_or_default()
+                    .to_string(),
+                start: ts_to_sec(chapter.start(), tb),
+                end: ts_to_sec(chapter.end(), tb),
+            }
+        })
+        .collect()
+}
+
+/// every marker starts a chapter lasting to the next one, the last one to end of file
+pub fn marker_chapters(markers: &[Marker], duration: f64) -> Vec<ChapterSpan> {
+    let mut markers: Vec<&Marker> = markers.iter().filter(|m| m.time < duration).collect();
+    markers.sort_by(|a, b| a.time.total_cmp(&b.time));
+    markers
+        .iter()
+        .enumerate()
+        .map(|(i, marker)| ChapterSpan {
+            title: marker.name.clone(),
+            start: marker.time,
+            end: markers.get(i + 1).map_or(duration, |next| next.time),
+        })
+        .collect()
+}
+
+/// part of chapter inside the range, relative to range start
+fn clip_chapter(chapter: &Range<f64>, range: &Range<f64>) -> Option<Range<f64>> {
+    let start = chapter.start.max(range.start);
+    let end = chapter.end.min(range.end);
+    (end > start).then(|| start - range.start..end - range.start)
+}
+
+/// seconds of the range the file has, open ranges end at the end of file
+pub fn range_length(input: &context::Input, range: &Range<f64>) -> f64 {
+    let duration = input.duration();
+    let end = if duration > 0 {
+        range
+            .end
+            .min(duration as f64 / ffmpeg_next::sys::AV_TIME_BASE as f64)
+    } else {
+        range.end
+    };
+    (end - range.start).max(0.)
+}
+
+/// add chapters in millisecond, has to be called before writing header
+pub fn write_chapters(
+    output: &mut context::Output,
+    chapters: &[ChapterSpan],
+) -> anyhow::Result<()> {
+    for (i, chapter) in chapters.iter().enumerate() {
+        output.add_chapter(
+            i as i64 + 1,
+            (1, 1000),
+            (chapter.start * 1000.).round() as i64,
+            (chapter.end * 1000.).round() as i64,
+            &chapter.title,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clip_chapter_rebases_to_range_start() {
+        assert_eq!(clip_chapter(&(5.0..20.0), &(10.0..30.0)), Some(0.0..10.0));
+        assert_eq!(clip_chapter(&(12.0..40.0), &(10.0..30.0)), Some(2.0..20.0));
+        assert_eq!(clip_chapter(&(0.0..10.0), &(10.0..30.0)), None);
+    }
+
+    #[test]
+    fn marker_chapters_run_to_next_marker() {
+        let markers = vec![
+            Marker {
+                name: "b".into(),
+                time: 40.,
+            },
+            Marker {
+                name: "a".into(),
+                time: 10.,
+            },
+        ];
+        let chapters = marker_chapters(&markers, 60.);
+        assert_eq!(chapters.len(), 2);
+        assert_eq!((chapters[0].start, chapters[0].end), (10., 40.));
+        assert_eq!(chapters[1].title, "b");
+        assert_eq!(chapters[1].end, 60.);
+    }
+}
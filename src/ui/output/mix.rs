@@ -0,0 +1,219 @@
+use std::{collections::VecDeque, path::PathBuf};
+
+use anyhow::anyhow;
+use ffmpeg_next::{codec, decoder, format, frame, media, software::resampling};
+
+use crate::{
+    config::ExportProfile,
+    ui::output::{
+        metadata::{
+            chapters_in, copy_global_metadata, copy_stream_metadata, range_length, write_chapters,
+        },
+        output::{
+            ExtraCopy, StreamShifter, add_extra_streams, open_output, ts_to_sec, write_header,
+        },
+        progress::ExportProgress,
+        transcode::{AudioEncoder, packed_samples},
+    },
+};
+
+/// one audio track decoded for mixing
+struct MixInput {
+    ix: usize,
+    gain: f32,
+    decoder: decoder::Audio,
+    resampler: Option<resampling::Context>,
+    decoded: frame::Audio,
+    // resampled samples with gain applied, waiting for other tracks
+    pending: VecDeque<f32>,
+}
+
+impl MixInput {
+    fn receive_frames(&mut self, encoder: &AudioEncoder) -> anyhow::Result<()> {
+        while self.decoder.receive_frame(&mut self.decoded).is_ok() {
+            let resampler = match self.resampler.as_mut() {
+                Some(r) => r,
+                None => self.resampler.insert(encoder.resampler_for(&self.decoder)?),
+            };
+            let mut resampled = frame::Audio::empty();
+            resampler.run(&self.decoded, &mut resampled)?;
+            let gain = self.gain;
+            self.pending.extend(
+                packed_samples(&resampled, encoder.channels)
+                    .into_iter()
+                    .map(|sample| sample * gain),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// copy the video of an exported file, if any, and replace its audio tracks by their sum,
+/// each track is weighted by the gain at same position and encoded with profile codec
+pub fn mix_audio(
+    path: &PathBuf,
+    out_path: &PathBuf,
+    video_ix: Option<usize>,
+    audio_ixs: &[usize],
+    gains: &[f32],
+    profile: &ExportProfile,
+    progress: &mut ExportProgress,
+) -> anyhow::Result<()> {
+    let mut input = ffmpeg_next::format::input(path)?;
+    let mut output = open_output(out_path, profile)?;
+    let global_header = output
+        .format()
+        .flags()
+        .contains(format::Flags::GLOBAL_HEADER);
+
+    let video_streams = match video_ix {
+        Some(ix) => {
+            let v = input
+                .stream(ix)
+                .ok_or(anyhow!("failed to get target stream"))?;
+            let mut v_out_stream = output.add_stream(None)?;
+            v_out_stream.set_parameters(v.parameters());
+            copy_stream_metadata(&v, &mut v_out_stream, false);
+            Some((v.time_base(), v_out_stream.index()))
+        }
+        None => None,
+    };
+
+    let mut inputs = vec![];
+    for (ix, gain) in audio_ixs.iter().zip(gains) {
+        let a = input
+            .stream(*ix)
+            .ok_or(anyhow!("failed to get target stream"))?;
+        let decoder = codec::context::Context::from_parameters(a.parameters())?
+            .decoder()
+            .audio()?;
+        inputs.push(MixInput {
+            ix: *ix,
+            gain: *gain,
+            decoder,
+            resampler: None,
+            decoded: frame::Audio::empty(),
+            pending: VecDeque::new(),
+        });
+    }
+    let first = inputs.first().ok_or(anyhow!("no audio track to mix"))?;
+
+    // mixed track keeps rate and metadata of the first track
+    let mut encoder = {
+        let a = input
+            .stream(first.ix)
+            .ok_or(anyhow!("failed to get target stream"))?;
+        let channels = inputs
+            .iter()
+            .map(|i| i.decoder.channels())
+            .max()
+            .unwrap_or(2);
+        AudioEncoder::new(
+            &a,
+            channels,
+            first.decoder.rate(),
+            &mut output,
+            profile,
+            global_header,
+        )?
+    };
+
+    // subtitles and other streams the premix carries follow the mixed track
+    let extra_ixs: Vec<usize> = input
+        .streams()
+        .filter(|s| Some(s.index()) != video_ix && s.parameters().medium() != media::Type::Audio)
+        .map(|s| s.index())
+        .collect();
+    let extra_out_ixs = add_extra_streams(&input, &extra_ixs, &mut output)?;
+    // the premix file is already stripped when asked
+    copy_global_metadata(&input, &mut output, false);
+    write_chapters(&mut output, &chapters_in(&input, &(0.0..f64::MAX), &[]))?;
+
+    write_header(&mut output, profile)?;
+    let mut video = match video_streams {
+        Some((video_tb, video_out_ix)) => {
+            let video_out_tb = output
+                .stream(video_out_ix)
+                .ok_or(anyhow!("failed to get timebase"))?
+                .time_base();
+            Some(StreamShifter::new(video_tb, video_out_ix, video_out_tb, 0))
+        }
+        None => None,
+    };
+    let mut audio = encoder.shifter(&output)?;
+    let mut extras = extra_out_ixs
+        .into_iter()
+        .map(|ixs| ExtraCopy::new(&input, &output, ixs, &(0.0..f64::MAX)))
+        .collect::<anyhow::Result<Vec<ExtraCopy>>>()?;
+
+    for (stream, mut packet) in input.packets() {
+        progress.update(ts_to_sec(packet.pts().unwrap_or(0), stream.time_base()))?;
+        if let Some(video) = video.as_mut().filter(|_| Some(stream.index()) == video_ix) {
+            video.write(&mut packet, &mut output)?;
+            continue;
+        }
+        if let Some(extra) = extras.iter_mut().find(|e| e.ix == stream.index()) {
+            extra.write(&mut packet, &mut output)?;
+            continue;
+        }
+        let Some(track) = inputs.iter_mut().find(|i| i.ix == stream.index()) else {
+            continue;
+        };
+        if let Err(e) = track.decoder.send_packet(&packet) {
+            eprintln!("mix audio decode failed: {e}");
+        }
+        track.receive_frames(&encoder)?;
+
+        let mixed = take_mixed(&mut pending_of(&mut inputs), false);
+        encoder.push(&mixed, &mut audio, &mut output)?;
+    }
+
+    for track in inputs.iter_mut() {
+        track.decoder.send_eof()?;
+        track.receive_frames(&encoder)?;
+    }
+    let mixed = take_mixed(&mut pending_of(&mut inputs), true);
+    encoder.push(&mixed, &mut audio, &mut output)?;
+    encoder.finish(&mut audio, &mut output)?;
+
+    output.write_trailer()?;
+    progress.finish_range(range_length(&input, &(0.0..f64::MAX)));
+    Ok(())
+}
+
+fn pending_of(inputs: &mut [MixInput]) -> Vec<&mut VecDeque<f32>> {
+    inputs.iter_mut().map(|i| &mut i.pending).collect()
+}
+
+/// sum the samples every track has, on flush shorter tracks are padded with silence
+fn take_mixed(pending: &mut [&mut VecDeque<f32>], flush: bool) -> Vec<f32> {
+    let lens = pending.iter().map(|p| p.len());
+    let len = if flush { lens.max() } else { lens.min() }.unwrap_or(0);
+    let mut mixed = vec![0.0; len];
+    for queue in pending.iter_mut() {
+        let n = len.min(queue.len());
+        for (m, sample) in mixed.iter_mut().zip(queue.drain(..n)) {
+            *m += sample;
+        }
+    }
+    for m in mixed.iter_mut() {
+        *m = m.clamp(-1.0, 1.0);
+    }
+    mixed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn take_mixed_pads_short_tracks_only_on_flush() {
+        let mut a = VecDeque::from(vec![0.5, -0.5, 0.25]);
+        let mut b = VecDeque::from(vec![0.25]);
+
+        assert_eq!(take_mixed(&mut [&mut a, &mut b], false), vec![0.75]);
+        assert_eq!(take_mixed(&mut [&mut a, &mut b], false), Vec::<f32>::new());
+        assert_eq!(take_mixed(&mut [&mut a, &mut b], true), vec![-0.5, 0.25]);
+        assert!(a.is_empty() && b.is_empty());
+    }
+}
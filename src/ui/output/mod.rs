@@ -1 +1,6 @@
+pub mod metadata;
+pub mod mix;
 pub mod output;
+pub mod progress;
+pub mod smart_cut;
+pub mod transcode;
@@ -1,112 +1,840 @@
-use std::{ops::Range, path::PathBuf};
+use std::{ffi::CString, fs, ops::Range, path::PathBuf};
 
-use anyhow::anyhow;
+use anyhow::{anyhow, bail};
+use ffmpeg_next::{
+    Dictionary, Packet, Rational, codec, ffi,
+    format::{self, context},
+};
+
+use crate::{
+    config::{Container, ExportProfile},
+    models::model::Marker,
+    ui::output::{
+        metadata::{
+            ChapterSpan, chapters_in, copy_global_metadata, copy_stream_metadata, marker_chapters,
+            range_length, write_chapters,
+        },
+        mix::mix_audio,
+        progress::{CancelToken, Cancelled, ExportProgress},
+        smart_cut::smart_cut,
+        transcode::{find_encoder, transcode},
+    },
+};
+
+/// everything needed to run an export in background
+#[derive(Debug, Clone)]
+pub struct OutputJob {
+    pub path: PathBuf,
+    pub out_path: PathBuf,
+    // none for audio only files
+    pub video_ix: Option<usize>,
+    pub audio_ixs: Vec<usize>,
+    // subtitle, attachment and data streams copied along
+    pub extra_ixs: Vec<usize>,
+    pub ranges: Vec<Range<f64>>,
+    pub profile: ExportProfile,
+    // export each range to its own file named by template
+    pub batch_template: Option<String>,
+    // mix audio tracks into one, gain of each track in audio_ixs order
+    pub audio_gains: Option<Vec<f32>>,
+    pub markers: Vec<Marker>,
+}
+
+impl OutputJob {
+    /// output files and the ranges written into each of them
+    pub fn targets(&self) -> Vec<(PathBuf, Vec<Range<f64>>)> {
+        let Some(template) = self.batch_template.as_ref() else {
+            return vec![(self.out_path.clone(), self.ranges.clone())];
+        };
+        let name = self
+            .path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or("output".into());
+        self.ranges
+            .iter()
+            .enumerate()
+            .map(|(i, range)| {
+                (
+                    batch_path(&self.out_path, template, &name, i),
+                    vec![range.clone()],
+                )
+            })
+            .collect()
+    }
+
+    /// run every target, one failed file does not stop the others,
+    /// on cancel the partial file is removed and remaining targets are skipped.
+    /// a finished file comes with notices of what was done differently than asked
+    pub fn run(
+        &self,
+        cancel: &CancelToken,
+        on_progress: impl Fn(f64),
+    ) -> Vec<(PathBuf, anyhow::Result<Vec<String>>)> {
+        if let Err(e) = self.validate() {
+            eprintln!("failed to export {:?}: {}", self.out_path, e);
+            return vec![(self.out_path.clone(), Err(e))];
+        }
+        let total = self
+            .targets()
+            .iter()
+            .map(|(_, ranges)| {
+                self.passes(ranges) * ranges.iter().map(|r| r.end - r.start).sum::<f64>()
+            })
+            .sum();
+        let mut progress = ExportProgress::new(total, cancel, &on_progress);
+        let mut results = vec![];
+        for (out_path, ranges) in self.targets() {
+            let result = self
+                .export(&out_path, &ranges, &mut progress)
+                .map(|()| progress.take_notices());
+            if let Err(e) = &result {
+                eprintln!("failed to export {:?}: {}", out_path, e);
+                if e.is::<Cancelled>() {
+                    let _ = fs::remove_file(&out_path);
+                    results.push((out_path, result));
+                    break;
+                }
+            }
+            results.push((out_path, result));
+        }
+        results
+    }
+
+    /// check codec of every exported stream against the container,
+    /// before anything is written
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let input = format::input(&self.path)?;
+        let stream_codec = |ix: usize| {
+            input
+                .stream(ix)
+                .map(|s| s.parameters().id())
+                .ok_or(anyhow!("failed to get target stream"))
+        };
+        // missing encoders are reported by the export itself
+        let encoder_codec = |names: &[&str]| find_encoder(names).map(|c| c.id());
+
+        let mut codecs = vec![];
+        if self.profile.copy_stream {
+            if let Some(ix) = self.video_ix {
+                codecs.push(stream_codec(ix)?);
+            }
+        } else if self.video_ix.is_some() {
+            codecs.extend(encoder_codec(self.profile.video_codec.encoder_names()));
+        } else {
+            bail!("audio only files can only be exported by stream copy");
+        }
+        if self.profile.copy_stream && self.audio_gains.is_none() {
+            for ix in self.audio_ixs.iter() {
+                codecs.push(stream_codec(*ix)?);
+            }
+        } else if !self.audio_ixs.is_empty() {
+            codecs.extend(encoder_codec(self.profile.audio_codec.encoder_names()));
+        }
+
+        // subtitles and attachments asked for would be dropped by the muxer
+        for ix in self.extra_ixs.iter() {
+            codecs.push(stream_codec(*ix)?);
+        }
+
+        let container = self.profile.container;
+        for id in codecs {
+            if !container_supports(container, id) {
+                bail!("{} can not be stored in {}", id.name(), container.label());
+            }
+        }
+        Ok(())
+    }
+
+    /// times the ranges of one file are written, joining parts and mixing audio
+    /// go over all of them again
+    fn passes(&self, ranges: &[Range<f64>]) -> f64 {
+        let mut passes = 1.;
+        if ranges.len() > 1 && joins_parts(self.video_ix, &self.profile) {
+            passes += 1.;
+        }
+        if self.audio_gains.is_some() {
+            passes += 1.;
+        }
+        passes
+    }
+
+    /// markers as chapters on the source timeline, empty keeps chapters of source
+    fn marker_chapters(&self) -> anyhow::Result<Vec<ChapterSpan>> {
+        if !self.profile.markers_as_chapters || self.markers.is_empty() {
+            return Ok(vec![]);
+        }
+        let input = format::input(&self.path)?;
+        let duration = range_length(&input, &(0.0..f64::MAX));
+        Ok(marker_chapters(&self.markers, duration))
+    }
+
+    /// export ranges into one file, audio tracks are mixed afterwards when gains are set
+    fn export(
+        &self,
+        out_path: &PathBuf,
+        ranges: &[Range<f64>],
+        progress: &mut ExportProgress,
+    ) -> anyhow::Result<()> {
+        let chapters = self.marker_chapters()?;
+        let Some(gains) = self.audio_gains.as_ref() else {
+            return output(
+                &self.path,
+                out_path,
+                self.video_ix,
+                &self.audio_ixs,
+                &self.extra_ixs,
+                ranges,
+                &chapters,
+                &self.profile,
+                progress,
+            );
+        };
+
+        let premix = temp_path(out_path, "premix");
+        let result = output(
+            &self.path,
+            &premix,
+            self.video_ix,
+            &self.audio_ixs,
+            &self.extra_ixs,
+            ranges,
+            &chapters,
+            &self.profile,
+            progress,
+        )
+        .and_then(|_| {
+            // premix has video first when there is one
+            let video_ix = self.video_ix.map(|_| 0);
+            let first_audio = video_ix.map_or(0, |_| 1);
+            let audio_ixs: Vec<usize> = (first_audio..first_audio + self.audio_ixs.len()).collect();
+            mix_audio(
+                &premix,
+                out_path,
+                video_ix,
+                &audio_ixs,
+                gains,
+                &self.profile,
+                progress,
+            )
+        });
+        let _ = fs::remove_file(&premix);
+        result
+    }
+}
+
+/// file for one range of batch export, `{name}` and `{index}` in template are replaced,
+/// folder and extension follow the output path
+pub fn batch_path(out_path: &PathBuf, template: &str, name: &str, ix: usize) -> PathBuf {
+    let index = format!("{:03}", ix + 1);
+    let mut file_name = template.replace("{name}", name);
+    if file_name.contains("{index}") {
+        file_name = file_name.replace("{index}", &index);
+    } else {
+        // keep every file unique
+        file_name.push('_');
+        file_name.push_str(&index);
+    }
+    if let Some(ext) = out_path.extension() {
+        file_name.push('.');
+        file_name.push_str(&ext.to_string_lossy());
+    }
+    out_path.with_file_name(file_name)
+}
 
 pub fn output(
     path: &PathBuf,
     out_path: &PathBuf,
-    target_video_ix: usize,
-    target_audio_ix: usize,
+    target_video_ix: Option<usize>,
+    target_audio_ixs: &[usize],
+    extra_ixs: &[usize],
+    ranges: &[Range<f64>],
+    chapters: &[ChapterSpan],
+    profile: &ExportProfile,
+    progress: &mut ExportProgress,
+) -> anyhow::Result<()> {
+    let Some(time_range) = ranges.first() else {
+        return Err(anyhow!("no range selected"));
+    };
+    if ranges.len() == 1 {
+        return output_range(
+            path,
+            out_path,
+            target_video_ix,
+            target_audio_ixs,
+            extra_ixs,
+            time_range,
+            chapters,
+            profile,
+            progress,
+        );
+    }
+    if !joins_parts(target_video_ix, profile) {
+        let segments: Vec<Segment> = ranges
+            .iter()
+            .map(|range| Segment {
+                path,
+                video_ix: target_video_ix,
+                audio_ixs: target_audio_ixs.to_vec(),
+                extra_ixs: extra_ixs.to_vec(),
+                range: range.clone(),
+                chapters,
+            })
+            .collect();
+        return concat(&segments, out_path, profile, progress);
+    }
+
+    // re-encoding modes export every range alone, then join the parts
+    let mut parts = vec![];
+    let result = (|| {
+        for (i, range) in ranges.iter().enumerate() {
+            let part = temp_path(out_path, &format!("part{}", i));
+            parts.push(part.clone());
+            output_range(
+                path,
+                &part,
+                target_video_ix,
+                target_audio_ixs,
+                extra_ixs,
+                range,
+                chapters,
+                profile,
+                progress,
+            )?;
+        }
+        // extra streams the muxer could not store are missing in parts
+        let streams = format::input(&parts[0])?.streams().count();
+        let audio_end = 1 + target_audio_ixs.len();
+        let segments: Vec<Segment> = parts
+            .iter()
+            .map(|part| Segment {
+                path: part,
+                video_ix: Some(0),
+                audio_ixs: (1..audio_end).collect(),
+                extra_ixs: (audio_end..streams).collect(),
+                range: 0.0..f64::MAX,
+                // parts already carry the chapters
+                chapters: &[],
+            })
+            .collect();
+        concat(&segments, out_path, profile, progress)
+    })();
+    for part in &parts {
+        let _ = fs::remove_file(part);
+    }
+    result
+}
+
+/// several ranges are exported alone and joined afterwards, unless all can be stream copied.
+/// audio has no frames to cut accurately, it is copied as is
+fn joins_parts(video_ix: Option<usize>, profile: &ExportProfile) -> bool {
+    !profile.copy_stream || (profile.frame_accurate && video_ix.is_some())
+}
+
+/// export one range with the mode chosen in profile
+fn output_range(
+    path: &PathBuf,
+    out_path: &PathBuf,
+    target_video_ix: Option<usize>,
+    target_audio_ixs: &[usize],
+    extra_ixs: &[usize],
     time_range: &Range<f64>,
+    chapters: &[ChapterSpan],
+    profile: &ExportProfile,
+    progress: &mut ExportProgress,
 ) -> anyhow::Result<()> {
-    println!(
-        "DEBUG: run output, path: {:?}, stream_ix: {}, time_range: {:?}",
-        path, target_video_ix, time_range
-    );
-    // open source & seek to start point
-    let mut input = ffmpeg_next::format::input(&path)?;
-    let ts = (ffmpeg_next::sys::AV_TIME_BASE as f64 * time_range.start) as i64;
-    input.seek(ts, ..ts)?;
+    match target_video_ix {
+        Some(video_ix) if !profile.copy_stream => transcode(
+            path,
+            out_path,
+            video_ix,
+            target_audio_ixs,
+            extra_ixs,
+            time_range,
+            chapters,
+            profile,
+            progress,
+        ),
+        Some(video_ix) if profile.frame_accurate => smart_cut(
+            path,
+            out_path,
+            video_ix,
+            target_audio_ixs,
+            extra_ixs,
+            time_range,
+            chapters,
+            profile,
+            progress,
+        ),
+        None if !profile.copy_stream => {
+            bail!("audio only files can only be exported by stream copy")
+        }
+        // audio has no frames to cut accurately, it is copied as is
+        _ => concat(
+            &[Segment {
+                path,
+                video_ix: target_video_ix,
+                audio_ixs: target_audio_ixs.to_vec(),
+                extra_ixs: extra_ixs.to_vec(),
+                range: time_range.clone(),
+                chapters,
+            }],
+            out_path,
+            profile,
+            progress,
+        ),
+    }
+}
+
+/// temporary file next to the output, tag is put before extension
+fn temp_path(out_path: &PathBuf, tag: &str) -> PathBuf {
+    let stem = out_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or("output".into());
+    let mut name = format!("{}.{}", stem, tag);
+    if let Some(ext) = out_path.extension() {
+        name.push('.');
+        name.push_str(&ext.to_string_lossy());
+    }
+    out_path.with_file_name(name)
+}
+
+/// a range of a source file, copied into the output
+pub struct Segment<'a> {
+    pub path: &'a PathBuf,
+    pub video_ix: Option<usize>,
+    pub audio_ixs: Vec<usize>,
+    pub extra_ixs: Vec<usize>,
+    pub range: Range<f64>,
+    // marker chapters on the source timeline
+    pub chapters: &'a [ChapterSpan],
+}
 
-    let mut output = ffmpeg_next::format::output(out_path)?;
+/// stream copy segments one after another with continuous timestamps
+pub fn concat(
+    segments: &[Segment],
+    out_path: &PathBuf,
+    profile: &ExportProfile,
+    progress: &mut ExportProgress,
+) -> anyhow::Result<()> {
+    let first = segments.first().ok_or(anyhow!("no range selected"))?;
+    let mut output = open_output(out_path, profile)?;
 
-    // create video stream
-    let video_out_ix;
+    // streams are created from the first segment, video first then audio and extras
+    let mut out_ixs = vec![];
+    let extra_count;
     {
-        let v = input
-            .stream(target_video_ix)
-            .ok_or(anyhow!("failed to get target stream"))?;
-        let mut v_out_stream = output.add_stream(None)?;
-        v_out_stream.set_parameters(v.parameters());
-        video_out_ix = v_out_stream.index();
+        let input = ffmpeg_next::format::input(first.path)?;
+        if let Some(ix) = first.video_ix {
+            let v = input
+                .stream(ix)
+                .ok_or(anyhow!("failed to get target stream"))?;
+            let mut v_out_stream = output.add_stream(None)?;
+            v_out_stream.set_parameters(v.parameters());
+            copy_stream_metadata(&v, &mut v_out_stream, profile.strip_metadata);
+            out_ixs.push(v_out_stream.index());
+        }
+
+        for ix in first.audio_ixs.iter() {
+            let a = input
+                .stream(*ix)
+                .ok_or(anyhow!("failed to get target stream"))?;
+            let mut a_out_stream = output.add_stream(None)?;
+            a_out_stream.set_parameters(a.parameters());
+            copy_stream_metadata(&a, &mut a_out_stream, profile.strip_metadata);
+            out_ixs.push(a_out_stream.index());
+        }
+
+        // following segments only carry the extra streams first one has
+        let extras = add_extra_streams(&input, &first.extra_ixs, &mut output)?;
+        out_ixs.extend(extras.iter().map(|(_, out_ix)| *out_ix));
+        extra_count = extras.len();
+        copy_global_metadata(&input, &mut output, profile.strip_metadata);
     }
-    // create audio stream
-    let audio_out_ix;
-    {
-        let a = input
-            .stream(target_audio_ix)
-            .ok_or(anyhow!("failed to get target stream"))?;
-        let mut a_out_stream = output.add_stream(None)?;
-        a_out_stream.set_parameters(a.parameters());
-        audio_out_ix = a_out_stream.index();
+    write_chapters(&mut output, &concat_chapters(segments)?)?;
+
+    write_header(&mut output, profile)?;
+    let mut shifters = vec![];
+    for out_ix in out_ixs {
+        let out_tb = output
+            .stream(out_ix)
+            .ok_or(anyhow!("failed to get timebase"))?
+            .time_base();
+        shifters.push(StreamShifter::new(out_tb, out_ix, out_tb, 0));
+    }
+    for segment in segments {
+        let extra_ixs = &segment.extra_ixs[..extra_count.min(segment.extra_ixs.len())];
+        let length = copy_segment(segment, extra_ixs, &mut output, &mut shifters, progress)?;
+        progress.finish_range(length);
+    }
+
+    output.write_trailer()?;
+    Ok(())
+}
+
+/// chapters of every segment, each segment placed after the previous one
+fn concat_chapters(segments: &[Segment]) -> anyhow::Result<Vec<ChapterSpan>> {
+    let mut chapters = vec![];
+    let mut offset = 0.;
+    for segment in segments {
+        let mut input = ffmpeg_next::format::input(segment.path)?;
+        // range starts this late in the written segment
+        offset += pre_roll(&mut input, segment)?;
+        chapters.extend(
+            chapters_in(&input, &segment.range, segment.chapters)
+                .into_iter()
+                .map(|c| ChapterSpan {
+                    start: c.start + offset,
+                    end: c.end + offset,
+                    ..c
+                }),
+        );
+        offset += range_length(&input, &segment.range);
+    }
+    Ok(chapters)
+}
+
+/// seconds copied before range start, as `copy_segment` starts at the keyframe before it
+fn pre_roll(input: &mut context::Input, segment: &Segment) -> anyhow::Result<f64> {
+    let Some(lead) = segment.video_ix.or(segment.audio_ixs.first().copied()) else {
+        return Ok(0.);
+    };
+    let ts = (ffmpeg_next::sys::AV_TIME_BASE as f64 * segment.range.start) as i64;
+    input.seek(ts, ..ts)?;
+    for (stream, packet) in input.packets() {
+        if stream.index() != lead {
+            continue;
+        }
+        let pkt_dts = packet.dts().or(packet.pts()).unwrap_or(0);
+        let lead_start = ts_to_sec(pkt_dts, stream.time_base());
+        return Ok((segment.range.start - lead_start).max(0.));
     }
+    Ok(0.)
+}
+
+fn copy_segment(
+    segment: &Segment,
+    extra_ixs: &[usize],
+    output: &mut context::Output,
+    shifters: &mut [StreamShifter],
+    progress: &ExportProgress,
+) -> anyhow::Result<f64> {
+    // open source & seek to start point
+    let mut input = ffmpeg_next::format::input(segment.path)?;
+    let ts = (ffmpeg_next::sys::AV_TIME_BASE as f64 * segment.range.start) as i64;
+    input.seek(ts, ..ts)?;
 
-    output.write_header()?;
-    let video_out_tb = output
-        .stream(video_out_ix)
-        .ok_or(anyhow!("failed to get timebase"))?
-        .time_base();
-    let audio_out_tb = output
-        .stream(audio_out_ix)
-        .ok_or(anyhow!("failed to get timebase"))?
-        .time_base();
+    // same order as shifters, first one leads: video, or first audio of audio only files
+    let mut targets: Vec<usize> = segment.video_ix.into_iter().collect();
+    targets.extend(segment.audio_ixs.iter().copied());
 
-    let mut v_offset: Option<i64> = None;
-    let mut a_offset: Option<i64> = None;
+    // sparse streams follow the lead, segment starts where lead will be placed
+    let segment_base = shifters[0].end_sec();
+    let mut lead_start = None;
+    let mut extra_started = vec![false; extra_ixs.len()];
 
-    let mut output_state = (false, false);
+    let mut started = vec![false; targets.len()];
+    let mut output_state = vec![false; targets.len()];
     for (stream, mut packet) in input.packets() {
+        if let Some(e) = extra_ixs.iter().position(|ix| *ix == stream.index()) {
+            let Some(lead_start) = lead_start else {
+                continue;
+            };
+            let tb = stream.time_base();
+            let start = sec_to_ts(lead_start, tb);
+            if !trim_to_range(&mut packet, start, sec_to_ts(segment.range.end, tb)) {
+                continue;
+            }
+            let shifter = &mut shifters[targets.len() + e];
+            if !extra_started[e] {
+                shifter.rebase_at(tb, start, segment_base);
+                extra_started[e] = true;
+            }
+            shifter.write(&mut packet, output)?;
+            continue;
+        }
+        let Some(pos) = targets.iter().position(|ix| *ix == stream.index()) else {
+            continue;
+        };
         let pkt_pts = packet.pts().unwrap_or(0);
         let pkt_dts = packet.dts().unwrap_or(pkt_pts);
-        let frame_time = pkt_pts as f64 / stream.time_base().denominator() as f64;
-        let this_ix = stream.index();
+        let frame_time = ts_to_sec(pkt_pts, stream.time_base());
+        if pos == 0 {
+            progress.update(frame_time - segment.range.start)?;
+        }
+
+        // when frame out the range
+        if frame_time > segment.range.end {
+            output_state[pos] = true;
+        }
+        if output_state.iter().all(|s| *s) {
+            break;
+        }
+        if output_state[pos] {
+            continue;
+        }
 
-        // when video frame out the range
-        if this_ix == target_video_ix && frame_time > time_range.end {
-            output_state.0 = true;
-        } else if this_ix == target_audio_ix && frame_time > time_range.end {
-            output_state.1 = true;
+        let shifter = &mut shifters[pos];
+        if !started[pos] {
+            // use DTS as the base offset
+            shifter.rebase(stream.time_base(), pkt_dts);
+            started[pos] = true;
+            if pos == 0 {
+                lead_start = Some(ts_to_sec(pkt_dts, stream.time_base()));
+            }
         }
+        shifter.write(&mut packet, output)?;
+    }
+    Ok(range_length(&input, &segment.range))
+}
+
+/// output file written with the muxer of chosen container
+pub fn open_output(out_path: &PathBuf, profile: &ExportProfile) -> anyhow::Result<context::Output> {
+    Ok(format::output_as(out_path, profile.container.muxer())?)
+}
+
+/// write header with muxer options of the container
+pub fn write_header(output: &mut context::Output, profile: &ExportProfile) -> anyhow::Result<()> {
+    let mut options = Dictionary::new();
+    for (key, value) in profile.container.muxer_options() {
+        options.set(key, value);
+    }
+    output.write_header_with(options)?;
+    Ok(())
+}
+
+/// whether the muxer of output can store streams of this codec, unknown counts as yes
+pub fn muxer_supports(output: &context::Output, id: codec::Id) -> bool {
+    query_codec(unsafe { output.format().as_ptr() }, id)
+}
 
-        if this_ix != target_video_ix && this_ix != target_audio_ix {
+/// same as `muxer_supports` without an opened output
+pub fn container_supports(container: Container, id: codec::Id) -> bool {
+    let Ok(name) = CString::new(container.muxer()) else {
+        return true;
+    };
+    let format = unsafe { ffi::av_guess_format(name.as_ptr(), std::ptr::null(), std::ptr::null()) };
+    if format.is_null() {
+        return true;
+    }
+    query_codec(format, id)
+}
+
+fn query_codec(format: *const ffi::AVOutputFormat, id: codec::Id) -> bool {
+    let result =
+        unsafe { ffi::avformat_query_codec(format, id.into(), codec::Compliance::Normal.into()) };
+    result != 0
+}
+
+/// add stream copies of the extra streams the muxer can store,
+/// returns source and output index of each added one
+pub fn add_extra_streams(
+    input: &context::Input,
+    extra_ixs: &[usize],
+    output: &mut context::Output,
+) -> anyhow::Result<Vec<(usize, usize)>> {
+    let mut added = vec![];
+    for ix in extra_ixs {
+        let stream = input
+            .stream(*ix)
+            .ok_or(anyhow!("failed to get target stream"))?;
+        let parameters = stream.parameters();
+        if !muxer_supports(output, parameters.id()) {
+            eprintln!(
+                "skip stream {} ({:?}), not supported by muxer",
+                ix,
+                parameters.id()
+            );
             continue;
         }
+        let mut out_stream = output.add_stream(None)?;
+        out_stream.set_parameters(parameters);
+        // attachments need their file name and mime type
+        out_stream.set_metadata(stream.metadata().to_owned());
+        added.push((*ix, out_stream.index()));
+    }
+    Ok(added)
+}
 
-        if output_state == (true, true) {
-            break;
+/// subtitle or data stream copied for a single range
+pub struct ExtraCopy {
+    pub ix: usize,
+    shifter: StreamShifter,
+    start: i64,
+    end: i64,
+}
+
+impl ExtraCopy {
+    /// output timeline starts at range start, as video does
+    pub fn new(
+        input: &context::Input,
+        output: &context::Output,
+        (ix, out_ix): (usize, usize),
+        range: &Range<f64>,
+    ) -> anyhow::Result<Self> {
+        let in_tb = input
+            .stream(ix)
+            .ok_or(anyhow!("failed to get target stream"))?
+            .time_base();
+        let out_tb = output
+            .stream(out_ix)
+            .ok_or(anyhow!("failed to get timebase"))?
+            .time_base();
+        let start = sec_to_ts(range.start, in_tb);
+        Ok(Self {
+            ix,
+            shifter: StreamShifter::new(in_tb, out_ix, out_tb, start),
+            start,
+            end: sec_to_ts(range.end, in_tb),
+        })
+    }
+
+    pub fn write(
+        &mut self,
+        packet: &mut Packet,
+        output: &mut context::Output,
+    ) -> anyhow::Result<()> {
+        if trim_to_range(packet, self.start, self.end) {
+            self.shifter.write(packet, output)?;
         }
+        Ok(())
+    }
+}
 
-        if this_ix == target_video_ix {
-            if v_offset.is_none() {
-                // use DTS as the base offset
-                v_offset = Some(pkt_dts);
-            }
-            let offset = v_offset.unwrap();
-            packet.set_pts(Some(pkt_pts - offset));
-            packet.set_dts(Some(pkt_dts - offset));
-            packet.set_stream(video_out_ix);
-            packet.rescale_ts(stream.time_base(), video_out_tb);
-        } else {
-            if a_offset.is_none() {
-                a_offset = Some(pkt_dts);
-            }
-            let offset = a_offset.unwrap();
-            packet.set_pts(Some(pkt_pts - offset));
-            packet.set_dts(Some(pkt_dts - offset));
-            packet.set_stream(audio_out_ix);
-            packet.rescale_ts(stream.time_base(), audio_out_tb);
+/// false when packet is outside start..=end, a cue lasting past end is cut there
+fn trim_to_range(packet: &mut Packet, start: i64, end: i64) -> bool {
+    let Some(pts) = packet.pts().or(packet.dts()) else {
+        return false;
+    };
+    if pts < start || pts > end {
+        return false;
+    }
+    if packet.duration() > end - pts {
+        packet.set_duration(end - pts);
+    }
+    true
+}
+
+/// convert second to timestamp in timebase
+pub fn sec_to_ts(sec: f64, time_base: Rational) -> i64 {
+    (sec * time_base.denominator() as f64 / time_base.numerator() as f64) as i64
+}
+
+/// convert timestamp in timebase to second
+pub fn ts_to_sec(ts: i64, time_base: Rational) -> f64 {
+    ts as f64 * time_base.numerator() as f64 / time_base.denominator() as f64
+}
+
+/// move packets of one source stream onto the output timeline
+pub struct StreamShifter {
+    in_tb: Rational,
+    out_ix: usize,
+    out_tb: Rational,
+    offset: i64,
+    base: i64,
+    end: i64,
+    last_dts: Option<i64>,
+}
+
+impl StreamShifter {
+    pub fn new(in_tb: Rational, out_ix: usize, out_tb: Rational, offset: i64) -> Self {
+        Self {
+            in_tb,
+            out_ix,
+            out_tb,
+            offset,
+            base: 0,
+            end: 0,
+            last_dts: None,
         }
+    }
 
-        if let (Some(p), Some(d)) = (packet.pts(), packet.dts()) {
-            if p < d {
-                packet.set_pts(Some(d));
-            }
+    /// continue with packets of next segment, placed after what was written
+    pub fn rebase(&mut self, in_tb: Rational, offset: i64) {
+        self.in_tb = in_tb;
+        self.offset = offset;
+        self.base = self.end;
+    }
+
+    /// continue with next segment placed at given output second, for sparse streams
+    /// whose own end says little about where the segment begins
+    pub fn rebase_at(&mut self, in_tb: Rational, offset: i64, base_sec: f64) {
+        self.in_tb = in_tb;
+        self.offset = offset;
+        self.base = sec_to_ts(base_sec, self.out_tb);
+    }
+
+    /// end of written packets in seconds
+    pub fn end_sec(&self) -> f64 {
+        ts_to_sec(self.end, self.out_tb)
+    }
+
+    pub fn write(
+        &mut self,
+        packet: &mut Packet,
+        output: &mut context::Output,
+    ) -> anyhow::Result<()> {
+        let pts = packet.pts();
+        let dts = packet.dts().or(pts);
+        packet.set_pts(pts.map(|p| p - self.offset));
+        packet.set_dts(dts.map(|d| d - self.offset));
+        packet.set_stream(self.out_ix);
+        packet.rescale_ts(self.in_tb, self.out_tb);
+        if self.base != 0 {
+            packet.set_pts(packet.pts().map(|p| p + self.base));
+            packet.set_dts(packet.dts().map(|d| d + self.base));
+        }
+
+        // keep dts increasing when copied and encoded packets meet
+        if let (Some(d), Some(last)) = (packet.dts(), self.last_dts)
+            && d <= last
+        {
+            packet.set_dts(Some(last + 1));
+        }
+        if let (Some(p), Some(d)) = (packet.pts(), packet.dts())
+            && p < d
+        {
+            packet.set_pts(Some(d));
+        }
+        self.last_dts = packet.dts();
+        if let Some(p) = packet.pts() {
+            self.end = self.end.max(p + packet.duration());
         }
 
         packet.set_position(-1);
-        packet.write_interleaved(&mut output)?;
+        packet.write_interleaved(output)?;
+        Ok(())
     }
+}
 
-    output.write_trailer()?;
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_path_replaces_name_and_index() {
+        let out = PathBuf::from("/clips/video_edit.mp4");
+        let path = batch_path(&out, "{name}_{index}", "video", 1);
+        assert_eq!(path, PathBuf::from("/clips/video_002.mp4"));
+    }
+
+    #[test]
+    fn trim_to_range_cuts_cue_at_range_end() {
+        let mut packet = Packet::empty();
+        packet.set_pts(Some(90));
+        packet.set_duration(30);
+        assert!(trim_to_range(&mut packet, 0, 100));
+        assert_eq!(packet.duration(), 10);
+
+        packet.set_pts(Some(101));
+        assert!(!trim_to_range(&mut packet, 0, 100));
+    }
+
+    #[test]
+    fn batch_path_without_index_appends_number() {
+        let out = PathBuf::from("/clips/video_edit.mkv");
+        let path = batch_path(&out, "highlight", "video", 0);
+        assert_eq!(path, PathBuf::from("/clips/highlight_001.mkv"));
+    }
 }
@@ -0,0 +1,86 @@
+use std::{
+    fmt,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+/// shared flag to stop a running export
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// error returned by export when it was cancelled
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "export cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// report progress of the whole job from the packet loops
+pub struct ExportProgress<'a> {
+    on_progress: &'a dyn Fn(f64),
+    cancel: &'a CancelToken,
+    // seconds of ranges finished before current one
+    done: f64,
+    total: f64,
+    // things the user should know about a finished file
+    notices: Vec<String>,
+}
+
+impl<'a> ExportProgress<'a> {
+    pub fn new(total: f64, cancel: &'a CancelToken, on_progress: &'a dyn Fn(f64)) -> Self {
+        Self {
+            on_progress,
+            cancel,
+            done: 0.,
+            total,
+            notices: vec![],
+        }
+    }
+
+    /// report seconds written from start of current range, fails when cancelled
+    pub fn update(&self, sec: f64) -> anyhow::Result<()> {
+        if self.cancel.is_cancelled() {
+            return Err(Cancelled.into());
+        }
+        if self.total > 0. {
+            (self.on_progress)(((self.done + sec.max(0.)) / self.total).clamp(0., 1.));
+        }
+        Ok(())
+    }
+
+    /// current range is written, following updates count after it
+    pub fn finish_range(&mut self, length: f64) {
+        self.done += length.max(0.);
+    }
+
+    /// note something the export did differently than asked
+    pub fn notice(&mut self, message: String) {
+        self.notices.push(message);
+    }
+
+    /// notices since last call, reported with the file they belong to
+    pub fn take_notices(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.notices)
+    }
+}
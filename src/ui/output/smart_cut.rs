@@ -0,0 +1,647 @@
+use std::{ops::Range, path::PathBuf};
+
+use anyhow::anyhow;
+use ffmpeg_next::{
+    Codec, Dictionary, Packet, Rational, codec, decoder, encoder, ffi, format, frame, picture,
+    software::scaling,
+};
+
+use crate::{
+    config::{Container, ExportProfile},
+    ui::output::{
+        metadata::{
+            ChapterSpan, chapters_in, copy_global_metadata, copy_side_data, copy_stream_metadata,
+            write_chapters,
+        },
+        output::{
+            ExtraCopy, StreamShifter, add_extra_streams, open_output, sec_to_ts, ts_to_sec,
+            write_header,
+        },
+        progress::ExportProgress,
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Head,
+    Copy,
+    Tail,
+    Done,
+}
+
+/// stream copied audio track, cut at packet level
+struct AudioCopy {
+    shifter: StreamShifter,
+    start: i64,
+    end: i64,
+    done: bool,
+}
+
+/// frame accurate export, only the partial GOPs on both edges of the range
+/// are re-encoded, the key frame aligned part in between is stream copied
+pub fn smart_cut(
+    path: &PathBuf,
+    out_path: &PathBuf,
+    target_video_ix: usize,
+    target_audio_ixs: &[usize],
+    extra_ixs: &[usize],
+    time_range: &Range<f64>,
+    chapters: &[ChapterSpan],
+    profile: &ExportProfile,
+    progress: &mut ExportProgress,
+) -> anyhow::Result<()> {
+    let mut input = ffmpeg_next::format::input(&path)?;
+
+    let (video_parameters, video_tb, frame_rate) = {
+        let v = input
+            .stream(target_video_ix)
+            .ok_or(anyhow!("failed to get target stream"))?;
+        let rate = v.avg_frame_rate();
+        (
+            v.parameters(),
+            v.time_base(),
+            (rate.numerator() > 0).then_some(rate),
+        )
+    };
+    let audio_tbs = target_audio_ixs
+        .iter()
+        .map(|ix| {
+            input
+                .stream(*ix)
+                .map(|a| a.time_base())
+                .ok_or(anyhow!("failed to get target stream"))
+        })
+        .collect::<anyhow::Result<Vec<Rational>>>()?;
+
+    let start_ts = sec_to_ts(time_range.start, video_tb);
+    let end_ts = sec_to_ts(time_range.end, video_tb);
+    // pts and dts of every key frame
+    let keyframes = scan_keyframes(&mut input, target_video_ix, time_range)?;
+    let keyframe_pts: Vec<i64> = keyframes.iter().map(|(pts, _)| *pts).collect();
+    let mut copy = plan_cut(&keyframe_pts, start_ts, end_ts);
+
+    // rewind to the key frame before start point
+    let ts = (ffmpeg_next::sys::AV_TIME_BASE as f64 * time_range.start) as i64;
+    input.seek(ts, ..ts)?;
+
+    let mut output = open_output(out_path, profile)?;
+    let global_header = output
+        .format()
+        .flags()
+        .contains(format::Flags::GLOBAL_HEADER);
+
+    // h264 and hevc edges carry their own parameter sets in band, so the copied part keeps
+    // the stream header of source. other codecs can only share it when the encoder
+    // makes the same one, else the whole range is re-encoded
+    let in_band =
+        copy.is_some() && matches!(video_parameters.id(), codec::Id::H264 | codec::Id::HEVC);
+    let edge_global_header = global_header && !in_band;
+    let mut head = SegmentEncoder::new(
+        &video_parameters,
+        video_tb,
+        frame_rate,
+        start_ts..end_ts + 1,
+        edge_global_header,
+    )?;
+    if edge_global_header && copy.is_some() && head.extradata() != extradata(&video_parameters) {
+        progress.notice(format!(
+            "{} can not be cut frame accurately in {}, the whole range was re-encoded",
+            video_parameters.id().name(),
+            profile.container.label()
+        ));
+        copy = None;
+    }
+    let length_size = if in_band {
+        nal_length_size(&video_parameters)
+    } else {
+        None
+    };
+    head.length_size = length_size;
+
+    let video_out_ix;
+    {
+        let v = input
+            .stream(target_video_ix)
+            .ok_or(anyhow!("failed to get target stream"))?;
+        let mut v_out_stream = output.add_stream(None)?;
+        if copy.is_some() {
+            v_out_stream.set_parameters(video_parameters.clone());
+            if in_band {
+                set_in_band_tag(&mut v_out_stream, profile.container);
+            }
+        } else {
+            v_out_stream.set_parameters(&head.encoder);
+            v_out_stream.set_time_base(video_tb);
+            copy_side_data(&v, &mut v_out_stream);
+        }
+        copy_stream_metadata(&v, &mut v_out_stream, profile.strip_metadata);
+        video_out_ix = v_out_stream.index();
+    }
+    let mut audio_out_ixs = vec![];
+    for ix in target_audio_ixs {
+        let a = input
+            .stream(*ix)
+            .ok_or(anyhow!("failed to get target stream"))?;
+        let mut a_out_stream = output.add_stream(None)?;
+        a_out_stream.set_parameters(a.parameters());
+        copy_stream_metadata(&a, &mut a_out_stream, profile.strip_metadata);
+        audio_out_ixs.push(a_out_stream.index());
+    }
+    let extra_out_ixs = add_extra_streams(&input, extra_ixs, &mut output)?;
+    copy_global_metadata(&input, &mut output, profile.strip_metadata);
+    write_chapters(&mut output, &chapters_in(&input, time_range, chapters))?;
+
+    write_header(&mut output, profile)?;
+    let video_out_tb = output
+        .stream(video_out_ix)
+        .ok_or(anyhow!("failed to get timebase"))?
+        .time_base();
+
+    let mut video = StreamShifter::new(video_tb, video_out_ix, video_out_tb, start_ts);
+    let mut audio = vec![];
+    for (audio_tb, audio_out_ix) in audio_tbs.iter().zip(audio_out_ixs) {
+        let audio_out_tb = output
+            .stream(audio_out_ix)
+            .ok_or(anyhow!("failed to get timebase"))?
+            .time_base();
+        let start = sec_to_ts(time_range.start, *audio_tb);
+        audio.push(AudioCopy {
+            shifter: StreamShifter::new(*audio_tb, audio_out_ix, audio_out_tb, start),
+            start,
+            end: sec_to_ts(time_range.end, *audio_tb),
+            done: false,
+        });
+    }
+    let mut extras = extra_out_ixs
+        .into_iter()
+        .map(|ixs| ExtraCopy::new(&input, &output, ixs, time_range))
+        .collect::<anyhow::Result<Vec<ExtraCopy>>>()?;
+
+    // the head only needs re-encoding when the range does not start on a key frame
+    let head_end = copy.as_ref().map_or(end_ts + 1, |c| c.start);
+    head.keep = start_ts..head_end;
+    // copied packets keep their timestamps right after the head, head is moved back
+    // by the reorder delay of copy so dts stays increasing where they meet
+    if let Some(c) = copy.as_ref() {
+        head.dts_shift = keyframes
+            .iter()
+            .find(|(pts, _)| *pts == c.start)
+            .map_or(0, |(pts, dts)| (pts - dts).max(0));
+    }
+    let mut segment = (start_ts < head_end).then_some(head);
+    // the last copied GOP is decoded by the tail too, leading pictures after the
+    // tail key frame of an open GOP refer to it
+    let tail_from = copy.as_ref().and_then(|c| {
+        keyframe_pts
+            .iter()
+            .copied()
+            .filter(|k| (c.start..c.end).contains(k))
+            .max()
+    });
+    // end of pts written by stream copy
+    let mut copied_end = None;
+
+    let mut phase = Phase::Head;
+    for (stream, mut packet) in input.packets() {
+        let this_ix = stream.index();
+
+        if let Some(extra) = extras.iter_mut().find(|e| e.ix == this_ix) {
+            extra.write(&mut packet, &mut output)?;
+        } else if let Some(a) = target_audio_ixs.iter().position(|ix| *ix == this_ix) {
+            let track = &mut audio[a];
+            let pts = packet.pts().unwrap_or(0);
+            if pts > track.end {
+                track.done = true;
+            } else if pts >= track.start {
+                track.shifter.write(&mut packet, &mut output)?;
+            }
+        } else if this_ix == target_video_ix && phase != Phase::Done {
+            let pts = packet.pts().unwrap_or(0);
+            let dts = packet.dts().unwrap_or(pts);
+            progress.update(ts_to_sec(dts, video_tb) - time_range.start)?;
+
+            if let Some(c) = copy.as_ref() {
+                if phase == Phase::Head && packet.is_key() && pts == c.start {
+                    if let Some(s) = segment.take() {
+                        s.finish(&mut video, &mut output)?;
+                    }
+                    phase = Phase::Copy;
+                }
+                if phase == Phase::Copy && packet.is_key() && Some(pts) == tail_from {
+                    let mut tail = SegmentEncoder::new(
+                        &video_parameters,
+                        video_tb,
+                        frame_rate,
+                        c.end..end_ts + 1,
+                        edge_global_header,
+                    )?;
+                    tail.length_size = length_size;
+                    segment = Some(tail);
+                }
+                if phase == Phase::Copy && packet.is_key() && pts == c.end {
+                    let mut tail = match segment.take() {
+                        Some(tail) => tail,
+                        None => {
+                            let mut tail = SegmentEncoder::new(
+                                &video_parameters,
+                                video_tb,
+                                frame_rate,
+                                c.end..end_ts + 1,
+                                edge_global_header,
+                            )?;
+                            tail.length_size = length_size;
+                            tail
+                        }
+                    };
+                    // leading pictures show before the key frame, but were not copied
+                    tail.keep = copied_end.unwrap_or(c.end).min(c.end)..end_ts + 1;
+                    segment = Some(tail);
+                    phase = Phase::Tail;
+                }
+            }
+
+            match phase {
+                Phase::Head | Phase::Tail => {
+                    if dts > end_ts {
+                        if let Some(s) = segment.take() {
+                            s.finish(&mut video, &mut output)?;
+                        }
+                        phase = Phase::Done;
+                    } else if let Some(s) = segment.as_mut() {
+                        s.send(&packet, &mut video, &mut output)?;
+                    }
+                }
+                Phase::Copy => {
+                    // tail decoder only takes references here, nothing is encoded yet
+                    if let Some(s) = segment.as_mut() {
+                        s.send(&packet, &mut video, &mut output)?;
+                    }
+                    // leading pictures of an open GOP belong to the re-encoded head
+                    if pts >= copy.as_ref().map_or(start_ts, |c| c.start) {
+                        video.write(&mut packet, &mut output)?;
+                        copied_end = copied_end.max(Some(pts + 1));
+                    }
+                }
+                Phase::Done => {}
+            }
+        }
+
+        if phase == Phase::Done && audio.iter().all(|a| a.done) {
+            break;
+        }
+    }
+
+    // reached the end of file before the end point
+    if let Some(s) = segment.take() {
+        s.finish(&mut video, &mut output)?;
+    }
+
+    output.write_trailer()?;
+    progress.finish_range(time_range.end - time_range.start);
+    Ok(())
+}
+
+/// collect pts and dts of video key frames from the key frame before start point to end point
+fn scan_keyframes(
+    input: &mut format::context::Input,
+    video_ix: usize,
+    time_range: &Range<f64>,
+) -> anyhow::Result<Vec<(i64, i64)>> {
+    let time_base = input
+        .stream(video_ix)
+        .ok_or(anyhow!("failed to get target stream"))?
+        .time_base();
+    let end = sec_to_ts(time_range.end, time_base);
+
+    let ts = (ffmpeg_next::sys::AV_TIME_BASE as f64 * time_range.start) as i64;
+    input.seek(ts, ..ts)?;
+
+    let mut keyframes = vec![];
+    for (stream, packet) in input.packets() {
+        if stream.index() != video_ix {
+            continue;
+        }
+        let Some(pts) = packet.pts() else {
+            continue;
+        };
+        let dts = packet.dts().unwrap_or(pts);
+        if dts > end {
+            break;
+        }
+        if packet.is_key() {
+            keyframes.push((pts, dts));
+        }
+    }
+    Ok(keyframes)
+}
+
+/// find the key frame aligned part of range which can be stream copied.
+/// copy starts at the first key frame in range and stops before the last one,
+/// the tail from the last key frame to end point is re-encoded.
+/// returns None when there is no key frame in range, the whole range is re-encoded
+pub fn plan_cut(keyframes: &[i64], start: i64, end: i64) -> Option<Range<i64>> {
+    let inside = keyframes
+        .iter()
+        .copied()
+        .filter(|k| (start..=end).contains(k));
+    let first = inside.clone().min()?;
+    let last = inside.max()?;
+    Some(first..last)
+}
+
+/// decode and re-encode one partial GOP with an encoder matching the source
+struct SegmentEncoder {
+    decoder: decoder::Video,
+    encoder: encoder::video::Encoder,
+    scaler: Option<scaling::Context>,
+    decoded: frame::Video,
+    converted: frame::Video,
+    // pts of frames which should be encoded
+    keep: Range<i64>,
+    // dts of encoded packets moved back by this
+    dts_shift: i64,
+    // annex b packets are rewritten with nal length of this size, as source stores them
+    length_size: Option<usize>,
+}
+
+impl SegmentEncoder {
+    fn new(
+        parameters: &codec::Parameters,
+        time_base: Rational,
+        frame_rate: Option<Rational>,
+        keep: Range<i64>,
+        global_header: bool,
+    ) -> anyhow::Result<Self> {
+        let decoder = codec::context::Context::from_parameters(parameters.clone())?
+            .decoder()
+            .video()?;
+        let codec = encoder::find(parameters.id()).ok_or(anyhow!(
+            "no encoder available for {:?}, smart cut is not supported",
+            parameters.id()
+        ))?;
+        let pixel_format = encoder_pixel_format(codec, decoder.format());
+
+        let mut video = codec::context::Context::new_with_codec(codec)
+            .encoder()
+            .video()?;
+        video.set_width(decoder.width());
+        video.set_height(decoder.height());
+        video.set_format(pixel_format);
+        video.set_aspect_ratio(decoder.aspect_ratio());
+        video.set_colorspace(decoder.color_space());
+        video.set_color_range(decoder.color_range());
+        video.set_time_base(time_base);
+        video.set_frame_rate(frame_rate);
+        // no reordering, so dts of the segment never goes back behind copied packets
+        video.set_max_b_frames(0);
+        if global_header {
+            video.set_flags(codec::Flags::GLOBAL_HEADER);
+        }
+        let bit_rate = unsafe { (*parameters.as_ptr()).bit_rate };
+        if bit_rate > 0 {
+            video.set_bit_rate(bit_rate as usize);
+        }
+
+        // other encoders keep their defaults and the source bitrate
+        let mut options = Dictionary::new();
+        if matches!(codec.name(), "libx264" | "libx265") {
+            options.set("crf", "18");
+            options.set("preset", "veryfast");
+        }
+        let encoder = video.open_as_with(codec, options)?;
+
+        let scaler = if pixel_format != decoder.format() {
+            Some(scaling::Context::get(
+                decoder.format(),
+                decoder.width(),
+                decoder.height(),
+                pixel_format,
+                decoder.width(),
+                decoder.height(),
+                scaling::Flags::BILINEAR,
+            )?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            decoder,
+            encoder,
+            scaler,
+            decoded: frame::Video::empty(),
+            converted: frame::Video::empty(),
+            keep,
+            dts_shift: 0,
+            length_size: None,
+        })
+    }
+
+    /// codec header of encoder, empty without global header
+    fn extradata(&self) -> &[u8] {
+        unsafe {
+            let ctx = self.encoder.as_ptr();
+            raw_bytes((*ctx).extradata, (*ctx).extradata_size)
+        }
+    }
+
+    fn send(
+        &mut self,
+        packet: &Packet,
+        shifter: &mut StreamShifter,
+        output: &mut format::context::Output,
+    ) -> anyhow::Result<()> {
+        if let Err(e) = self.decoder.send_packet(packet) {
+            eprintln!("smart cut decode failed: {e}");
+        }
+        self.receive_frames(shifter, output)
+    }
+
+    /// flush decoder and encoder, write remain packets
+    fn finish(
+        mut self,
+        shifter: &mut StreamShifter,
+        output: &mut format::context::Output,
+    ) -> anyhow::Result<()> {
+        self.decoder.send_eof()?;
+        self.receive_frames(shifter, output)?;
+        self.encoder.send_eof()?;
+        self.receive_packets(shifter, output)
+    }
+
+    fn receive_frames(
+        &mut self,
+        shifter: &mut StreamShifter,
+        output: &mut format::context::Output,
+    ) -> anyhow::Result<()> {
+        while self.decoder.receive_frame(&mut self.decoded).is_ok() {
+            let Some(pts) = self.decoded.timestamp() else {
+                continue;
+            };
+            if !self.keep.contains(&pts) {
+                continue;
+            }
+
+            let frame = if let Some(scaler) = self.scaler.as_mut() {
+                scaler.run(&self.decoded, &mut self.converted)?;
+                &mut self.converted
+            } else {
+                &mut self.decoded
+            };
+            frame.set_pts(Some(pts));
+            frame.set_kind(picture::Type::None);
+            self.encoder.send_frame(frame)?;
+            self.receive_packets(shifter, output)?;
+        }
+        Ok(())
+    }
+
+    fn receive_packets(
+        &mut self,
+        shifter: &mut StreamShifter,
+        output: &mut format::context::Output,
+    ) -> anyhow::Result<()> {
+        let mut packet = Packet::empty();
+        while self.encoder.receive_packet(&mut packet).is_ok() {
+            if let Some(size) = self.length_size {
+                let mut converted =
+                    Packet::copy(&length_prefixed(packet.data().unwrap_or(&[]), size));
+                converted.set_pts(packet.pts());
+                converted.set_dts(packet.dts());
+                converted.set_duration(packet.duration());
+                converted.set_flags(packet.flags());
+                packet = converted;
+            }
+            packet.set_dts(packet.dts().map(|d| d - self.dts_shift));
+            shifter.write(&mut packet, output)?;
+        }
+        Ok(())
+    }
+}
+
+/// codec header of stream, avcC or hvcC for mp4 and mkv sources
+fn extradata(parameters: &codec::Parameters) -> &[u8] {
+    unsafe {
+        let par = parameters.as_ptr();
+        raw_bytes((*par).extradata, (*par).extradata_size)
+    }
+}
+
+/// size of nal length field when source stores length prefixed nal units (avcC or hvcC),
+/// none for annex b
+fn nal_length_size(parameters: &codec::Parameters) -> Option<usize> {
+    let data = extradata(parameters);
+    let field = match parameters.id() {
+        codec::Id::H264 => 4,
+        codec::Id::HEVC => 21,
+        _ => return None,
+    };
+    (data.len() > field && data[0] == 1).then(|| (data[field] & 3) as usize + 1)
+}
+
+/// split annex b data at start codes and put the length of each nal unit before it
+fn length_prefixed(data: &[u8], length_size: usize) -> Vec<u8> {
+    let mut starts = vec![];
+    let mut i = 0;
+    while i + 3 <= data.len() {
+        if data[i..i + 3] == [0, 0, 1] {
+            starts.push(i + 3);
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+    let mut out = Vec::with_capacity(data.len() + starts.len() * length_size);
+    for (n, start) in starts.iter().enumerate() {
+        let mut end = starts.get(n + 1).map_or(data.len(), |next| next - 3);
+        // zero bytes before a start code are not part of the nal
+        while end > *start && data[end - 1] == 0 {
+            end -= 1;
+        }
+        let len = (end - start) as u32;
+        out.extend_from_slice(&len.to_be_bytes()[4 - length_size..]);
+        out.extend_from_slice(&data[*start..end]);
+    }
+    out
+}
+
+/// mp4 and mov mark streams whose parameter sets may change in band as avc3 or hev1
+fn set_in_band_tag(stream: &mut format::stream::StreamMut, container: Container) {
+    if !matches!(container, Container::Mp4 | Container::Mov) {
+        return;
+    }
+    unsafe {
+        let par = (*stream.as_mut_ptr()).codecpar;
+        let tag = match (*par).codec_id {
+            ffi::AVCodecID::AV_CODEC_ID_H264 => *b"avc3",
+            ffi::AVCodecID::AV_CODEC_ID_HEVC => *b"hev1",
+            _ => return,
+        };
+        (*par).codec_tag = u32::from_le_bytes(tag);
+    }
+}
+
+unsafe fn raw_bytes<'a>(data: *const u8, size: i32) -> &'a [u8] {
+    if data.is_null() || size <= 0 {
+        return &[];
+    }
+    unsafe { std::slice::from_raw_parts(data, size as usize) }
+}
+
+/// use the source pixel format if encoder supports it
+fn encoder_pixel_format(codec: Codec, source: format::Pixel) -> format::Pixel {
+    let Some(formats) = codec.video().ok().and_then(|v| v.formats()) else {
+        return source;
+    };
+    let formats: Vec<format::Pixel> = formats.collect();
+    if formats.is_empty() || formats.contains(&source) {
+        source
+    } else {
+        formats[0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plan_cut_copies_between_first_and_last_key_frame_in_range() {
+        let keyframes = [0, 250, 500, 750, 1000];
+
+        assert_eq!(plan_cut(&keyframes, 100, 900), Some(250..750));
+        assert_eq!(plan_cut(&keyframes, 250, 1000), Some(250..1000));
+    }
+
+    #[test]
+    fn plan_cut_reencodes_whole_range_without_key_frame_inside() {
+        let keyframes = [0, 250];
+
+        assert_eq!(plan_cut(&keyframes, 10, 200), None);
+        assert_eq!(plan_cut(&[], 10, 200), None);
+    }
+
+    #[test]
+    fn length_prefixed_replaces_start_codes() {
+        let annex_b = [
+            0, 0, 0, 1, 0x67, 0xAA, 0, 0, 1, 0x68, 0xBB, 0xCC, 0, 0, 0, 1, 0x65,
+        ];
+
+        assert_eq!(
+            length_prefixed(&annex_b, 4),
+            vec![
+                0, 0, 0, 2, 0x67, 0xAA, 0, 0, 0, 3, 0x68, 0xBB, 0xCC, 0, 0, 0, 1, 0x65
+            ]
+        );
+        assert_eq!(
+            length_prefixed(&[0, 0, 1, 0x68, 0xBB], 2),
+            vec![0, 2, 0x68, 0xBB]
+        );
+    }
+
+    #[test]
+    fn plan_cut_with_single_key_frame_has_empty_copy() {
+        let keyframes = [0, 250, 500];
+
+        assert_eq!(plan_cut(&keyframes, 100, 400), Some(250..250));
+    }
+}
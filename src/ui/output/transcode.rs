@@ -0,0 +1,612 @@
+use std::{ops::Range, path::PathBuf};
+
+use anyhow::anyhow;
+use ffmpeg_next::{
+    ChannelLayout, Codec, Dictionary, Packet, Rational, codec, decoder, encoder,
+    format::{self, sample::Type},
+    frame, picture,
+    software::{resampling, scaling},
+};
+
+use crate::{
+    config::{ExportProfile, RateControl},
+    ui::output::{
+        metadata::{
+            ChapterSpan, chapters_in, copy_global_metadata, copy_side_data, copy_stream_metadata,
+            write_chapters,
+        },
+        output::{
+            ExtraCopy, StreamShifter, add_extra_streams, open_output, sec_to_ts, ts_to_sec,
+            write_header,
+        },
+        progress::ExportProgress,
+    },
+};
+
+/// decode the selected range and re-encode it with codecs of the export profile
+pub fn transcode(
+    path: &PathBuf,
+    out_path: &PathBuf,
+    target_video_ix: usize,
+    target_audio_ixs: &[usize],
+    extra_ixs: &[usize],
+    time_range: &Range<f64>,
+    chapters: &[ChapterSpan],
+    profile: &ExportProfile,
+    progress: &mut ExportProgress,
+) -> anyhow::Result<()> {
+    let mut input = ffmpeg_next::format::input(&path)?;
+    let ts = (ffmpeg_next::sys::AV_TIME_BASE as f64 * time_range.start) as i64;
+    input.seek(ts, ..ts)?;
+
+    let mut output = open_output(out_path, profile)?;
+    let global_header = output
+        .format()
+        .flags()
+        .contains(format::Flags::GLOBAL_HEADER);
+
+    let mut video = {
+        let v = input
+            .stream(target_video_ix)
+            .ok_or(anyhow!("failed to get target stream"))?;
+        VideoTranscoder::new(&v, &mut output, profile, global_header, time_range)?
+    };
+    let mut audio = vec![];
+    for ix in target_audio_ixs {
+        let a = input
+            .stream(*ix)
+            .ok_or(anyhow!("failed to get target stream"))?;
+        audio.push(AudioTranscoder::new(
+            &a,
+            &mut output,
+            profile,
+            global_header,
+            time_range,
+        )?);
+    }
+    let extra_out_ixs = add_extra_streams(&input, extra_ixs, &mut output)?;
+    copy_global_metadata(&input, &mut output, profile.strip_metadata);
+    write_chapters(&mut output, &chapters_in(&input, time_range, chapters))?;
+
+    write_header(&mut output, profile)?;
+    let mut video_shifter = video.shifter(&output)?;
+    let mut audio_shifters = audio
+        .iter()
+        .map(|a| a.shifter(&output))
+        .collect::<anyhow::Result<Vec<StreamShifter>>>()?;
+    let mut extras = extra_out_ixs
+        .into_iter()
+        .map(|ixs| ExtraCopy::new(&input, &output, ixs, time_range))
+        .collect::<anyhow::Result<Vec<ExtraCopy>>>()?;
+
+    let mut video_done = false;
+    let mut audio_done = vec![false; audio.len()];
+    for (stream, mut packet) in input.packets() {
+        let this_ix = stream.index();
+        if let Some(extra) = extras.iter_mut().find(|e| e.ix == this_ix) {
+            extra.write(&mut packet, &mut output)?;
+        } else if this_ix == target_video_ix && !video_done {
+            let pts = packet.pts().unwrap_or(0);
+            progress.update(ts_to_sec(pts, stream.time_base()) - time_range.start)?;
+            if packet.dts().unwrap_or(pts) > video.end_ts {
+                video_done = true;
+            } else {
+                video.send(&packet, &mut video_shifter, &mut output)?;
+            }
+        } else if let Some(a) = target_audio_ixs.iter().position(|ix| *ix == this_ix)
+            && !audio_done[a]
+        {
+            let pts = packet.pts().unwrap_or(0);
+            if ts_to_sec(pts, audio[a].in_tb) > time_range.end {
+                audio_done[a] = true;
+            } else {
+                audio[a].send(&packet, &mut audio_shifters[a], &mut output)?;
+            }
+        }
+
+        if video_done && audio_done.iter().all(|d| *d) {
+            break;
+        }
+    }
+
+    video.finish(&mut video_shifter, &mut output)?;
+    for (a, shifter) in audio.iter_mut().zip(audio_shifters.iter_mut()) {
+        a.finish(shifter, &mut output)?;
+    }
+
+    output.write_trailer()?;
+    progress.finish_range(time_range.end - time_range.start);
+    Ok(())
+}
+
+/// find the first encoder available in this ffmpeg build
+pub fn find_encoder(names: &[&str]) -> Option<Codec> {
+    names.iter().find_map(|name| encoder::find_by_name(name))
+}
+
+/// prefer yuv420p which every platform can play
+fn preferred_pixel_format(codec: Codec, source: format::Pixel) -> format::Pixel {
+    let Some(formats) = codec.video().ok().and_then(|v| v.formats()) else {
+        return format::Pixel::YUV420P;
+    };
+    let formats: Vec<format::Pixel> = formats.collect();
+    if formats.is_empty() || formats.contains(&format::Pixel::YUV420P) {
+        format::Pixel::YUV420P
+    } else if formats.contains(&source) {
+        source
+    } else {
+        formats[0]
+    }
+}
+
+fn preferred_sample_rate(codec: Codec, source: u32) -> u32 {
+    let Some(rates) = codec.audio().ok().and_then(|a| a.rates()) else {
+        return source;
+    };
+    let rates: Vec<i32> = rates.collect();
+    if rates.is_empty() || rates.contains(&(source as i32)) {
+        source
+    } else if rates.contains(&48000) {
+        48000
+    } else {
+        rates[0] as u32
+    }
+}
+
+struct VideoTranscoder {
+    decoder: decoder::Video,
+    encoder: encoder::video::Encoder,
+    scaler: Option<scaling::Context>,
+    decoded: frame::Video,
+    converted: frame::Video,
+    in_tb: Rational,
+    out_ix: usize,
+    start_ts: i64,
+    end_ts: i64,
+}
+
+impl VideoTranscoder {
+    fn new(
+        stream: &format::stream::Stream,
+        output: &mut format::context::Output,
+        profile: &ExportProfile,
+        global_header: bool,
+        time_range: &Range<f64>,
+    ) -> anyhow::Result<Self> {
+        let decoder = codec::context::Context::from_parameters(stream.parameters())?
+            .decoder()
+            .video()?;
+        let codec = find_encoder(profile.video_codec.encoder_names()).ok_or(anyhow!(
+            "no encoder available for {}",
+            profile.video_codec.label()
+        ))?;
+        let pixel_format = preferred_pixel_format(codec, decoder.format());
+        let time_base = stream.time_base();
+        let rate = stream.avg_frame_rate();
+
+        let mut video = codec::context::Context::new_with_codec(codec)
+            .encoder()
+            .video()?;
+        video.set_width(decoder.width());
+        video.set_height(decoder.height());
+        video.set_format(pixel_format);
+        video.set_aspect_ratio(decoder.aspect_ratio());
+        video.set_time_base(time_base);
+        video.set_frame_rate((rate.numerator() > 0).then_some(rate));
+        if global_header {
+            video.set_flags(codec::Flags::GLOBAL_HEADER);
+        }
+
+        let mut options = Dictionary::new();
+        match profile.rate_control {
+            RateControl::Crf => {
+                let crf = profile.crf.min(profile.video_codec.max_crf());
+                options.set("crf", &crf.to_string());
+                // libvpx only uses constant quality mode without a target bitrate
+                video.set_bit_rate(0);
+            }
+            RateControl::Bitrate => {
+                video.set_bit_rate(profile.video_bitrate as usize * 1000);
+            }
+        }
+        if matches!(codec.name(), "libx264" | "libx265") {
+            options.set("preset", "medium");
+        }
+        let encoder = video.open_as_with(codec, options)?;
+
+        let mut out_stream = output.add_stream(codec)?;
+        out_stream.set_parameters(&encoder);
+        out_stream.set_time_base(time_base);
+        copy_stream_metadata(stream, &mut out_stream, profile.strip_metadata);
+        // frames are encoded as stored, players rotate them by the display matrix
+        copy_side_data(stream, &mut out_stream);
+        let out_ix = out_stream.index();
+
+        let scaler = if pixel_format != decoder.format() {
+            Some(scaling::Context::get(
+                decoder.format(),
+                decoder.width(),
+                decoder.height(),
+                pixel_format,
+                decoder.width(),
+                decoder.height(),
+                scaling::Flags::BILINEAR,
+            )?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            decoder,
+            encoder,
+            scaler,
+            decoded: frame::Video::empty(),
+            converted: frame::Video::empty(),
+            in_tb: time_base,
+            out_ix,
+            start_ts: sec_to_ts(time_range.start, time_base),
+            end_ts: sec_to_ts(time_range.end, time_base),
+        })
+    }
+
+    fn shifter(&self, output: &format::context::Output) -> anyhow::Result<StreamShifter> {
+        let out_tb = output
+            .stream(self.out_ix)
+            .ok_or(anyhow!("failed to get timebase"))?
+            .time_base();
+        Ok(StreamShifter::new(
+            self.in_tb,
+            self.out_ix,
+            out_tb,
+            self.start_ts,
+        ))
+    }
+
+    fn send(
+        &mut self,
+        packet: &Packet,
+        shifter: &mut StreamShifter,
+        output: &mut format::context::Output,
+    ) -> anyhow::Result<()> {
+        if let Err(e) = self.decoder.send_packet(packet) {
+            eprintln!("transcode video decode failed: {e}");
+        }
+        self.receive_frames(shifter, output)
+    }
+
+    fn finish(
+        &mut self,
+        shifter: &mut StreamShifter,
+        output: &mut format::context::Output,
+    ) -> anyhow::Result<()> {
+        self.decoder.send_eof()?;
+        self.receive_frames(shifter, output)?;
+        self.encoder.send_eof()?;
+        self.receive_packets(shifter, output)
+    }
+
+    fn receive_frames(
+        &mut self,
+        shifter: &mut StreamShifter,
+        output: &mut format::context::Output,
+    ) -> anyhow::Result<()> {
+        while self.decoder.receive_frame(&mut self.decoded).is_ok() {
+            let Some(pts) = self.decoded.timestamp() else {
+                continue;
+            };
+            if pts < self.start_ts || pts > self.end_ts {
+                continue;
+            }
+
+            let frame = if let Some(scaler) = self.scaler.as_mut() {
+                scaler.run(&self.decoded, &mut self.converted)?;
+                &mut self.converted
+            } else {
+                &mut self.decoded
+            };
+            frame.set_pts(Some(pts));
+            frame.set_kind(picture::Type::None);
+            self.encoder.send_frame(frame)?;
+            self.receive_packets(shifter, output)?;
+        }
+        Ok(())
+    }
+
+    fn receive_packets(
+        &mut self,
+        shifter: &mut StreamShifter,
+        output: &mut format::context::Output,
+    ) -> anyhow::Result<()> {
+        let mut packet = Packet::empty();
+        while self.encoder.receive_packet(&mut packet).is_ok() {
+            shifter.write(&mut packet, output)?;
+        }
+        Ok(())
+    }
+}
+
+struct AudioTranscoder {
+    decoder: decoder::Audio,
+    // decoded frame to packed f32 in encoder rate and layout
+    resampler: resampling::Context,
+    decoded: frame::Audio,
+    encoder: AudioEncoder,
+    in_tb: Rational,
+    time_range: Range<f64>,
+}
+
+impl AudioTranscoder {
+    fn new(
+        stream: &format::stream::Stream,
+        output: &mut format::context::Output,
+        profile: &ExportProfile,
+        global_header: bool,
+        time_range: &Range<f64>,
+    ) -> anyhow::Result<Self> {
+        let decoder = codec::context::Context::from_parameters(stream.parameters())?
+            .decoder()
+            .audio()?;
+        let encoder = AudioEncoder::new(
+            stream,
+            decoder.channels(),
+            decoder.rate(),
+            output,
+            profile,
+            global_header,
+        )?;
+        let resampler = encoder.resampler_for(&decoder)?;
+
+        Ok(Self {
+            decoder,
+            resampler,
+            decoded: frame::Audio::empty(),
+            encoder,
+            in_tb: stream.time_base(),
+            time_range: time_range.clone(),
+        })
+    }
+
+    fn shifter(&self, output: &format::context::Output) -> anyhow::Result<StreamShifter> {
+        self.encoder.shifter(output)
+    }
+
+    fn send(
+        &mut self,
+        packet: &Packet,
+        shifter: &mut StreamShifter,
+        output: &mut format::context::Output,
+    ) -> anyhow::Result<()> {
+        if let Err(e) = self.decoder.send_packet(packet) {
+            eprintln!("transcode audio decode failed: {e}");
+        }
+        self.receive_frames(shifter, output)
+    }
+
+    fn finish(
+        &mut self,
+        shifter: &mut StreamShifter,
+        output: &mut format::context::Output,
+    ) -> anyhow::Result<()> {
+        self.decoder.send_eof()?;
+        self.receive_frames(shifter, output)?;
+        self.encoder.finish(shifter, output)
+    }
+
+    fn receive_frames(
+        &mut self,
+        shifter: &mut StreamShifter,
+        output: &mut format::context::Output,
+    ) -> anyhow::Result<()> {
+        while self.decoder.receive_frame(&mut self.decoded).is_ok() {
+            let Some(pts) = self.decoded.timestamp() else {
+                continue;
+            };
+            let frame_start = ts_to_sec(pts, self.in_tb);
+            if frame_start > self.time_range.end {
+                continue;
+            }
+
+            let mut resampled = frame::Audio::empty();
+            self.resampler.run(&self.decoded, &mut resampled)?;
+            let channels = self.encoder.channels;
+            let samples = packed_samples(&resampled, channels);
+
+            // trim samples out of range, so audio is as accurate as video
+            let rate = self.encoder.rate as f64;
+            let skip = ((self.time_range.start - frame_start) * rate).max(0.0) as usize;
+            let keep = ((self.time_range.end - frame_start) * rate).max(0.0) as usize;
+            let from = (skip * channels).min(samples.len());
+            let to = (keep * channels).min(samples.len()).max(from);
+            self.encoder.push(&samples[from..to], shifter, output)?;
+        }
+        Ok(())
+    }
+}
+
+/// encode interleaved f32 samples into one output audio stream
+pub struct AudioEncoder {
+    encoder: encoder::audio::Encoder,
+    // packed f32 to encoder sample format
+    converter: resampling::Context,
+    // interleaved samples waiting for a full encoder frame
+    fifo: Vec<f32>,
+    layout: ChannelLayout,
+    pub channels: usize,
+    pub rate: u32,
+    frame_size: usize,
+    next_pts: i64,
+    out_ix: usize,
+}
+
+impl AudioEncoder {
+    /// add an output stream with the audio codec of profile, at most stereo,
+    /// metadata is taken from the source stream
+    pub fn new(
+        stream: &format::stream::Stream,
+        channels: u16,
+        source_rate: u32,
+        output: &mut format::context::Output,
+        profile: &ExportProfile,
+        global_header: bool,
+    ) -> anyhow::Result<Self> {
+        let codec = find_encoder(profile.audio_codec.encoder_names()).ok_or(anyhow!(
+            "no encoder available for {}",
+            profile.audio_codec.label()
+        ))?;
+
+        let channels = channels.clamp(1, 2);
+        let layout = ChannelLayout::default(i32::from(channels));
+        let rate = preferred_sample_rate(codec, source_rate);
+        let sample_format = codec
+            .audio()?
+            .formats()
+            .and_then(|mut f| f.next())
+            .unwrap_or(format::Sample::F32(Type::Planar));
+
+        let mut audio = codec::context::Context::new_with_codec(codec)
+            .encoder()
+            .audio()?;
+        audio.set_rate(rate as i32);
+        audio.set_channel_layout(layout);
+        audio.set_format(sample_format);
+        audio.set_bit_rate(profile.audio_bitrate as usize * 1000);
+        audio.set_time_base((1, rate as i32));
+        audio.compliance(codec::Compliance::Experimental);
+        if global_header {
+            audio.set_flags(codec::Flags::GLOBAL_HEADER);
+        }
+        let encoder = audio.open_as(codec)?;
+        let frame_size = match encoder.frame_size() {
+            0 => 1024,
+            size => size as usize,
+        };
+
+        let mut out_stream = output.add_stream(codec)?;
+        out_stream.set_parameters(&encoder);
+        out_stream.set_time_base((1, rate as i32));
+        copy_stream_metadata(stream, &mut out_stream, profile.strip_metadata);
+        let out_ix = out_stream.index();
+
+        let converter = resampling::Context::get(
+            format::Sample::F32(Type::Packed),
+            layout,
+            rate,
+            sample_format,
+            layout,
+            rate,
+        )?;
+
+        Ok(Self {
+            encoder,
+            converter,
+            fifo: Vec::new(),
+            layout,
+            channels: channels as usize,
+            rate,
+            frame_size,
+            next_pts: 0,
+            out_ix,
+        })
+    }
+
+    /// resampler from decoded frames to packed f32 this encoder takes
+    pub fn resampler_for(&self, decoder: &decoder::Audio) -> anyhow::Result<resampling::Context> {
+        Ok(resampling::Context::get(
+            decoder.format(),
+            decoder.channel_layout(),
+            decoder.rate(),
+            format::Sample::F32(Type::Packed),
+            self.layout,
+            self.rate,
+        )?)
+    }
+
+    pub fn shifter(&self, output: &format::context::Output) -> anyhow::Result<StreamShifter> {
+        let out_tb = output
+            .stream(self.out_ix)
+            .ok_or(anyhow!("failed to get timebase"))?
+            .time_base();
+        Ok(StreamShifter::new(
+            Rational::new(1, self.rate as i32),
+            self.out_ix,
+            out_tb,
+            0,
+        ))
+    }
+
+    /// queue interleaved samples, full frames are encoded at once
+    pub fn push(
+        &mut self,
+        samples: &[f32],
+        shifter: &mut StreamShifter,
+        output: &mut format::context::Output,
+    ) -> anyhow::Result<()> {
+        self.fifo.extend_from_slice(samples);
+        self.encode_fifo(false, shifter, output)
+    }
+
+    /// encode remaining samples and drain the encoder
+    pub fn finish(
+        &mut self,
+        shifter: &mut StreamShifter,
+        output: &mut format::context::Output,
+    ) -> anyhow::Result<()> {
+        self.encode_fifo(true, shifter, output)?;
+        self.encoder.send_eof()?;
+        self.receive_packets(shifter, output)
+    }
+
+    /// send full frames in fifo to encoder, remain samples are sent when flush
+    fn encode_fifo(
+        &mut self,
+        flush: bool,
+        shifter: &mut StreamShifter,
+        output: &mut format::context::Output,
+    ) -> anyhow::Result<()> {
+        let chunk_len = self.frame_size * self.channels;
+        while self.fifo.len() >= chunk_len || (flush && !self.fifo.is_empty()) {
+            let len = chunk_len.min(self.fifo.len());
+            let chunk: Vec<f32> = self.fifo.drain(..len).collect();
+            let samples = len / self.channels;
+
+            let mut packed =
+                frame::Audio::new(format::Sample::F32(Type::Packed), samples, self.layout);
+            packed.set_rate(self.rate);
+            for (dst, sample) in packed.data_mut(0).chunks_exact_mut(4).zip(chunk) {
+                dst.copy_from_slice(&sample.to_ne_bytes());
+            }
+
+            let mut converted = frame::Audio::empty();
+            self.converter.run(&packed, &mut converted)?;
+            converted.set_pts(Some(self.next_pts));
+            self.next_pts += samples as i64;
+
+            self.encoder.send_frame(&converted)?;
+            self.receive_packets(shifter, output)?;
+        }
+        Ok(())
+    }
+
+    fn receive_packets(
+        &mut self,
+        shifter: &mut StreamShifter,
+        output: &mut format::context::Output,
+    ) -> anyhow::Result<()> {
+        let mut packet = Packet::empty();
+        while self.encoder.receive_packet(&mut packet).is_ok() {
+            shifter.write(&mut packet, output)?;
+        }
+        Ok(())
+    }
+}
+
+/// read interleaved f32 samples of a packed audio frame
+pub fn packed_samples(frame: &frame::Audio, channels: usize) -> Vec<f32> {
+    if frame.samples() == 0 {
+        return vec![];
+    }
+    frame.data(0)[..frame.samples() * channels * 4]
+        .chunks_exact(4)
+        .map(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
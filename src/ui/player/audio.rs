@@ -1,6 +1,6 @@
 use std::sync::{
     Arc,
-    atomic::{AtomicBool, Ordering},
+    atomic::{AtomicBool, AtomicU64, Ordering},
 };
 
 use anyhow::{anyhow, bail};
@@ -78,6 +78,7 @@ impl AudioPlayer {
         consumer: HeapCons<f32>,
         signal: Arc<AtomicBool>,
         gain: Arc<AtomicF32>,
+        consumed: Arc<AtomicU64>,
     ) -> anyhow::Result<()> {
         macro_rules! build_stream {
             ($sample_type:ty) => {
@@ -87,6 +88,7 @@ impl AudioPlayer {
                     consumer,
                     signal,
                     gain,
+                    consumed,
                     move |err| eprintln!("audio output stream error: {err}"),
                 )
             };
@@ -123,6 +125,8 @@ fn build_output_stream<T>(
     mut consumer: HeapCons<f32>,
     signal: Arc<AtomicBool>,
     gain: Arc<AtomicF32>,
+    // samples taken from buffer, drives playback clock
+    consumed: Arc<AtomicU64>,
     error_callback: impl FnMut(cpal::StreamError) + Send + 'static,
 ) -> Result<cpal::Stream, cpal::BuildStreamError>
 where
@@ -132,7 +136,8 @@ where
         config,
         move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
             let gain = gain.load(Ordering::Relaxed);
-            write_samples(data, &mut consumer, gain);
+            let read = write_samples(data, &mut consumer, gain);
+            consumed.fetch_add(read as u64, Ordering::Release);
             signal.store(true, Ordering::Release);
         },
         error_callback,
@@ -140,16 +145,22 @@ where
     )
 }
 
-fn write_samples<T>(data: &mut [T], consumer: &mut HeapCons<f32>, gain: f32)
+/// fill output from buffer, silence when it runs dry, returns samples taken
+fn write_samples<T>(data: &mut [T], consumer: &mut HeapCons<f32>, gain: f32) -> usize
 where
     T: SizedSample + FromSample<f32>,
 {
+    let mut read = 0;
     for output in data {
-        *output = consumer
-            .try_pop()
-            .map(|sample| T::from_sample(sample * gain))
-            .unwrap_or(T::EQUILIBRIUM);
+        *output = match consumer.try_pop() {
+            Some(sample) => {
+                read += 1;
+                T::from_sample(sample * gain)
+            }
+            None => T::EQUILIBRIUM,
+        };
     }
+    read
 }
 
 #[cfg(test)]
@@ -168,9 +179,10 @@ mod tests {
         producer.push_slice(&[-1.0, 0.0, 1.0]);
 
         let mut output = [i16::MIN; 4];
-        write_samples(&mut output, &mut consumer, 1.0);
+        let read = write_samples(&mut output, &mut consumer, 1.0);
 
         assert_eq!(output, [i16::MIN, 0, i16::MAX, 0]);
+        assert_eq!(read, 3);
     }
 
     #[test]
@@ -11,7 +11,8 @@ use std::{
     time::Duration,
 };
 
-use anyhow::anyhow;
+use anyhow::{anyhow, bail};
+use atomic_float::AtomicF32;
 use ffmpeg_next::{
     ChannelLayout, Codec, Error, Packet, Rational,
     decoder::{self},
@@ -38,8 +39,9 @@ use crate::{
     models::model::OutputParams,
     ui::{
         player::{
-            model::{AudioRail, FrameImage},
+            model::{AudioRail, ExtraStream, FrameImage, MixTrack},
             size::PlayerSize,
+            tempo::Tempo,
             utils::generate_image_fallback,
         },
         views::app::MyApp,
@@ -54,8 +56,19 @@ pub enum DecoderEvent {
     Seek(f64),
     LastKey(f64),
     NextKey(f64),
+    // decode the next frame while paused, then pause again
+    Step,
+    // decode again up to the frame at this time, from a few frames before it
+    StepBack(f64),
 }
 
+// packets buffered per audio track before reading pauses
+const AUDIO_QUEUE_SIZE: usize = 100;
+/// frames before the shown one decoded again when stepping back
+pub static STEP_CACHE_FRAMES: usize = 8;
+/// frame rate of empty frames audio only files are played on
+static TICK_RATE: i32 = 25;
+
 #[derive(Debug)]
 pub struct ResamplerParams {
     format: format::Sample,
@@ -330,15 +343,13 @@ enum HardwareFailure {
 pub struct VideoDecoder {
     path: PathBuf,
     input: Option<context::Input>,
-    video_stream_ix: usize,
-    audio_stream_ix: usize,
+    video_stream_ix: Option<usize>,
+    audio_stream_ix: Option<usize>,
     video_parameters: ffmpeg_next::codec::Parameters,
-    audio_parameters: ffmpeg_next::codec::Parameters,
     v_decoder: Option<decoder::Video>,
     hw_selection: Option<Box<HwSelection>>,
-    a_decoder: Option<decoder::Audio>,
     time_base: Rational,
-    audio_time_base: Rational,
+    frame_rate: Rational,
     duration: i64,
     device_sample_rate: u32,
     device_channels: u16,
@@ -351,6 +362,8 @@ pub struct VideoDecoder {
     event: Arc<Mutex<DecoderEvent>>,
     condvar: Arc<Condvar>,
     decode_mode: Arc<AtomicU8>,
+    // playback speed, audio is stretched to it
+    playback_rate: Arc<AtomicF32>,
 }
 
 impl VideoDecoder {
@@ -365,6 +378,12 @@ impl VideoDecoder {
         self
     }
 
+    /// share playback rate of player, taken by decoder on every seek
+    pub fn set_playback_rate(mut self, rate: Arc<AtomicF32>) -> Self {
+        self.playback_rate = rate;
+        self
+    }
+
     /// set DecoderEvent
     pub fn set_event(&mut self, new: DecoderEvent) {
         let mut event = self.event.lock().unwrap();
@@ -377,6 +396,11 @@ impl VideoDecoder {
         self.time_base
     }
 
+    /// length of one frame in seconds
+    pub fn frame_duration(&self) -> f64 {
+        frame_duration(self.frame_rate)
+    }
+
     pub fn get_duration(&self) -> Option<i64> {
         if !self.duration.is_positive() {
             return None;
@@ -398,20 +422,24 @@ impl VideoDecoder {
     {
         let i = ffmpeg_next::format::input(path)?;
 
-        let v_stream = i
-            .streams()
-            .best(ffmpeg_next::media::Type::Video)
-            .ok_or(anyhow!("failed to find best video stream"))?;
-
+        // audio only files play on empty frames, files without audio play video only
+        let v_stream = i.streams().best(ffmpeg_next::media::Type::Video);
         let a_stream = i
             .streams()
-            .find(|s| s.parameters().medium() == ffmpeg_next::media::Type::Audio)
-            // .best(ffmpeg_next::media::Type::Audio)
-            .ok_or(anyhow!("failed to find video stream"))?;
+            .find(|s| s.parameters().medium() == ffmpeg_next::media::Type::Audio);
+        if v_stream.is_none() && a_stream.is_none() {
+            bail!("failed to find video or audio stream");
+        }
+        let video_ix = v_stream.as_ref().map(|s| s.index());
 
         let mut rails: Vec<AudioRail> = vec![];
+        let mut extra_streams: Vec<ExtraStream> = vec![];
         for (i, s) in i.streams().into_iter().enumerate() {
-            if s.index() == v_stream.index() {
+            if Some(s.index()) == video_ix {
+                continue;
+            }
+            if s.parameters().medium() != ffmpeg_next::media::Type::Audio {
+                extra_streams.extend(ExtraStream::from_stream(&s));
                 continue;
             }
             let handler_name = match s.metadata().get("handler_name") {
@@ -427,30 +455,44 @@ impl VideoDecoder {
             });
         }
 
-        let video_parameters = v_stream.parameters();
-        let audio_parameters = a_stream.parameters();
-        let gpu_policy = cx.global::<AppConfig>().gpu_policy;
-        let (v_decoder, hw_selection) = open_video_decoder(video_parameters.clone(), gpu_policy)?;
-
-        let a_decoder = open_audio_decoder(audio_parameters.clone())?;
-
-        let time_base = v_stream.time_base();
-        let audio_time_base = a_stream.time_base();
         let duration = i.duration();
-        // get original video size
-        let original_width = v_decoder.width();
-        let original_height = v_decoder.height();
-
-        size.update(cx, |s, _| {
-            s.set_original((original_width, original_height));
-        });
+        let (video_parameters, v_decoder, hw_selection, time_base, frame_rate) = match v_stream {
+            Some(v_stream) => {
+                let video_parameters = v_stream.parameters();
+                let gpu_policy = cx.global::<AppConfig>().gpu_policy;
+                let (v_decoder, hw_selection) =
+                    open_video_decoder(video_parameters.clone(), gpu_policy)?;
+
+                // get original video size
+                let original_width = v_decoder.width();
+                let original_height = v_decoder.height();
+                size.update(cx, |s, _| {
+                    s.set_original((original_width, original_height));
+                });
+                (
+                    video_parameters,
+                    Some(v_decoder),
+                    hw_selection,
+                    v_stream.time_base(),
+                    v_stream.avg_frame_rate(),
+                )
+            }
+            None => (
+                ffmpeg_next::codec::Parameters::new(),
+                None,
+                None,
+                Rational::new(1, TICK_RATE),
+                Rational::new(TICK_RATE, 1),
+            ),
+        };
 
         // update related output params
         output_prarms.update(cx, |p, _| {
             p.path = Some(path.clone());
-            p.video_stream_ix = Some(v_stream.index());
-            p.audio_stream_ix = Some(a_stream.index());
+            p.video_stream_ix = video_ix;
+            p.audio_stream_ix = a_stream.as_ref().map(|s| s.index());
             p.audio_rails = Some(rails);
+            p.extra_streams = extra_streams;
         });
 
         let decode_mode = if hw_selection.is_some() {
@@ -461,15 +503,13 @@ impl VideoDecoder {
 
         Ok(Self {
             path: path.clone(),
-            video_stream_ix: v_stream.index(),
-            audio_stream_ix: a_stream.index(),
+            video_stream_ix: video_ix,
+            audio_stream_ix: a_stream.map(|s| s.index()),
             video_parameters,
-            audio_parameters,
-            v_decoder: Some(v_decoder),
+            v_decoder,
             hw_selection,
-            a_decoder: Some(a_decoder),
             time_base,
-            audio_time_base,
+            frame_rate,
             duration,
             v_producer: None,
             a_producer: None,
@@ -482,18 +522,7 @@ impl VideoDecoder {
             event: Arc::new(Mutex::new(DecoderEvent::None)),
             condvar: Arc::new(Condvar::new()),
             decode_mode: Arc::new(AtomicU8::new(decode_mode.as_u8())),
-        })
-    }
-
-    fn resampler_params(&self) -> anyhow::Result<ResamplerParams> {
-        let Some(a_decoder) = self.a_decoder.as_ref() else {
-            return Err(anyhow!("none decoder"));
-        };
-        Ok(ResamplerParams {
-            format: a_decoder.format(),
-            source_rate: a_decoder.rate(),
-            target_format: format::Sample::F32(Type::Packed),
-            target_rate: self.device_sample_rate,
+            playback_rate: Arc::new(AtomicF32::new(1.0)),
         })
     }
 
@@ -521,16 +550,15 @@ impl VideoDecoder {
         )?)
     }
 
-    /// spawn decoder thread
+    /// spawn decoder thread, tracks in mix are summed by gain, or only audio_ix is played when empty
     /// TODO: let decoder take VideoDecoder struct, use only handle to control
     pub fn spawn_decoder(
         &mut self,
         size: Entity<PlayerSize>,
         cx: &mut Context<MyApp>,
         audio_ix: Option<usize>,
+        mix: &[MixTrack],
     ) {
-        let resampler_params = self.resampler_params().unwrap();
-
         let Some(mut input) = self.input.take() else {
             return;
         };
@@ -539,82 +567,100 @@ impl VideoDecoder {
             .as_ref()
             .map(|selection| selection.pixel_format);
 
-        let Some(mut v_decoder) = self.v_decoder.take() else {
-            return;
-        };
-        let Some(mut a_decoder) = self.a_decoder.take() else {
-            return;
-        };
+        let mut v_decoder = self.v_decoder.take();
         let Some(mut v_producer) = self.v_producer.take() else {
             return;
         };
         let Some(mut a_producer) = self.a_producer.take() else {
             return;
         };
-        if let Some(ix) = audio_ix {
-            self.audio_stream_ix = ix;
+        if let Some(ix) = audio_ix.filter(|_| self.audio_stream_ix.is_some()) {
+            self.audio_stream_ix = Some(ix);
             self.output_prarms.update(cx, |p, _| {
                 p.audio_stream_ix = Some(ix);
             });
         }
 
         let time_base = self.time_base;
-        let audio_time_base = self.audio_time_base;
-        let device_channels = self.device_channels;
-
+        let frame_duration = self.frame_duration();
+        let device_channel_layout = output_channel_layout(self.device_channels);
+
+        let mix = if mix.is_empty() {
+            self.audio_stream_ix
+                .map(|ix| MixTrack { ix, gain: 1.0 })
+                .into_iter()
+                .collect()
+        } else {
+            mix.to_vec()
+        };
+        let mut tracks = vec![];
+        for m in mix {
+            let Some(stream) = input.stream(m.ix) else {
+                continue;
+            };
+            match AudioTrack::open(
+                &stream,
+                m.gain,
+                self.device_sample_rate,
+                device_channel_layout,
+            ) {
+                Ok(track) => tracks.push(track),
+                Err(e) => eprintln!("failed to open audio track {}: {e}", m.ix),
+            }
+        }
         let original_size = size.read(cx).original_size();
 
         let video_ix = self.video_stream_ix;
-        let audio_ix = self.audio_stream_ix;
 
-        let w = v_decoder.width();
-        let h = v_decoder.height();
+        let (w, h) = v_decoder
+            .as_ref()
+            .map_or((1, 1), |d| (d.width(), d.height()));
+        let mut ticks = Ticks::new(self.duration);
         let event = self.event.clone();
         let condvar = self.condvar.clone();
         let path = self.path.clone();
         let video_parameters = self.video_parameters.clone();
-        let audio_parameters = self.audio_parameters.clone();
         let decode_mode = self.decode_mode.clone();
+        let playback_rate = self.playback_rate.clone();
+        let device_sample_rate = self.device_sample_rate;
+        let device_channels = self.device_channels;
 
         thread::spawn(move || {
-            let device_channel_layout = output_channel_layout(device_channels);
             let mut scaler = None;
-            let mut resampler_params = resampler_params;
             let mut w = w;
             let mut h = h;
 
-            let mut resampler = Self::create_resampler(
-                a_decoder.channel_layout(),
-                device_channel_layout,
-                &resampler_params,
-            )
-            .unwrap();
-
             // frame buffer
             let mut next_video_frame: Option<FrameImage> = None;
             let mut next_audio_sample: Option<Vec<f32>> = None;
 
             let mut video_pkt_queue: VecDeque<Packet> = VecDeque::new();
-            let mut audio_pkt_queue: VecDeque<Packet> = VecDeque::new();
             // frame varible
             let mut decoded_frame = ffmpeg_next::frame::Video::empty();
             let mut hardware_frame = ffmpeg_next::frame::Video::empty();
             let mut scaled_frame = ffmpeg_next::frame::Video::new(format::Pixel::BGRA, w, h);
-            let mut decoded_audio = ffmpeg_next::frame::Audio::empty();
-            let mut resampled_audio = ffmpeg_next::frame::Audio::empty();
 
             let mut seeking_to: Option<f64> = None;
             let mut seek_state = (false, false);
             let mut is_read_finished = false;
             let mut first_video_frame_pushed = false;
+            // paused again once the stepped frame is pushed
+            let mut stepping = false;
+            let mut tempo = Tempo::new(device_sample_rate, device_channels);
+            tempo.reset(playback_rate.load(Ordering::Relaxed) as f64);
 
             loop {
                 {
                     let mut need_flash = false;
                     // handle decoder event
                     let mut event = event.lock().unwrap();
+                    stepping = match *event {
+                        DecoderEvent::Step => true,
+                        DecoderEvent::None => stepping,
+                        _ => false,
+                    };
                     match *event {
-                        DecoderEvent::None => (),
+                        DecoderEvent::None | DecoderEvent::Step => (),
                         DecoderEvent::Stop => break,
                         DecoderEvent::Pause => {
                             let _event = condvar.wait(event).unwrap();
@@ -656,37 +702,54 @@ impl VideoDecoder {
                             seek_state = (false, false);
                             need_flash = true;
                         }
+                        DecoderEvent::StepBack(t) => {
+                            // frames before `from` are decoded from keyframe but not scaled
+                            let from = (t - frame_duration * STEP_CACHE_FRAMES as f64).max(0.0);
+                            let ts = (ffmpeg_next::sys::AV_TIME_BASE as f64 * from) as i64;
+                            if let Err(e) = input.seek(ts, ..ts) {
+                                eprintln!("video seek failed: {e}");
+                                continue;
+                            }
+
+                            is_read_finished = false;
+                            seeking_to = Some(from);
+                            seek_state = (false, false);
+                            need_flash = true;
+                        }
                     }
                     if need_flash {
-                        v_decoder.flush();
-                        a_decoder.flush();
+                        if let Some(v_decoder) = v_decoder.as_mut() {
+                            v_decoder.flush();
+                        }
                         video_pkt_queue.clear();
-                        audio_pkt_queue.clear();
-
-                        // create new resampler
-                        resampler = Self::create_resampler(
-                            a_decoder.channel_layout(),
-                            device_channel_layout,
-                            &resampler_params,
-                        )
-                        .unwrap();
+                        next_audio_sample = None;
+                        for track in tracks.iter_mut() {
+                            if let Err(e) = track.flush(device_channel_layout) {
+                                eprintln!("audio track flush failed: {e}");
+                            }
+                        }
 
                         unsafe {
                             a_producer.set_write_index(a_producer.read_index());
                         }
+                        tempo.reset(playback_rate.load(Ordering::Relaxed) as f64);
                     }
                     *event = DecoderEvent::None;
                 }
                 // if no enough pkts, read from file
                 while !is_read_finished
-                    && (video_pkt_queue.len() < 50 || audio_pkt_queue.len() < 100)
+                    && ((video_ix.is_some() && video_pkt_queue.len() < 50)
+                        || (!tracks.is_empty()
+                            && tracks.iter().all(|t| t.queue.len() < AUDIO_QUEUE_SIZE)))
                 {
                     // read packets
                     if let Some((stream, packet)) = input.packets().next() {
-                        if stream.index() == video_ix {
+                        if Some(stream.index()) == video_ix {
                             video_pkt_queue.push_back(packet);
-                        } else if stream.index() == audio_ix {
-                            audio_pkt_queue.push_back(packet);
+                        } else if let Some(track) =
+                            tracks.iter_mut().find(|t| t.ix == stream.index())
+                        {
+                            track.queue.push_back(packet);
                         }
                     } else {
                         is_read_finished = true;
@@ -699,23 +762,24 @@ impl VideoDecoder {
                 if let Some(to) = seeking_to {
                     let target =
                         (to * time_base.denominator() as f64 / time_base.numerator() as f64) as i64;
-                    let audio_target = (to * audio_time_base.denominator() as f64
-                        / audio_time_base.numerator() as f64)
-                        as i64;
                     if !seek_state.0 {
-                        match handle_video(
-                            &mut video_pkt_queue,
-                            &mut v_decoder,
-                            &mut decoded_frame,
-                            &mut hardware_frame,
-                            &mut scaler,
-                            &mut scaled_frame,
-                            w,
-                            h,
-                            original_size,
-                            Some(target),
-                            hardware_pixel_format,
-                        ) {
+                        let decoded = match v_decoder.as_mut() {
+                            Some(v_decoder) => handle_video(
+                                &mut video_pkt_queue,
+                                v_decoder,
+                                &mut decoded_frame,
+                                &mut hardware_frame,
+                                &mut scaler,
+                                &mut scaled_frame,
+                                w,
+                                h,
+                                original_size,
+                                Some(target),
+                                hardware_pixel_format,
+                            ),
+                            None => ticks.frame(Some(target)),
+                        };
+                        match decoded {
                             VideoDecodeResult::Frame(frame) => {
                                 next_video_frame = Some(frame);
                                 seek_state.0 = true;
@@ -730,37 +794,34 @@ impl VideoDecoder {
                         }
                     }
                     if !seek_state.1 {
-                        let result = handle_audio(
-                            &mut audio_pkt_queue,
-                            &mut a_decoder,
-                            &mut resampler,
-                            &mut decoded_audio,
-                            &mut resampled_audio,
-                            Some(audio_target),
-                        );
-                        if result.is_some() {
-                            next_audio_sample = result;
-                            seek_state.1 = true;
+                        for track in tracks.iter_mut().filter(|t| !t.seeked) {
+                            track.decode(Some(to));
                         }
+                        let ahead = tracks_ahead(&tracks, is_read_finished);
+                        seek_state.1 = tracks.iter().all(|t| t.seeked || t.ended(ahead));
                     }
                     if seek_state == (true, true) {
                         seeking_to = None;
                     }
                 } else {
                     if next_video_frame.is_none() {
-                        match handle_video(
-                            &mut video_pkt_queue,
-                            &mut v_decoder,
-                            &mut decoded_frame,
-                            &mut hardware_frame,
-                            &mut scaler,
-                            &mut scaled_frame,
-                            w,
-                            h,
-                            original_size,
-                            None,
-                            hardware_pixel_format,
-                        ) {
+                        let decoded = match v_decoder.as_mut() {
+                            Some(v_decoder) => handle_video(
+                                &mut video_pkt_queue,
+                                v_decoder,
+                                &mut decoded_frame,
+                                &mut hardware_frame,
+                                &mut scaler,
+                                &mut scaled_frame,
+                                w,
+                                h,
+                                original_size,
+                                None,
+                                hardware_pixel_format,
+                            ),
+                            None => ticks.frame(None),
+                        };
+                        match decoded {
                             VideoDecodeResult::Frame(frame) => next_video_frame = Some(frame),
                             VideoDecodeResult::HardwareStartupFailed(error) => {
                                 hardware_failure = Some(HardwareFailure::Startup(error));
@@ -772,14 +833,12 @@ impl VideoDecoder {
                         }
                     }
                     if next_audio_sample.is_none() {
-                        next_audio_sample = handle_audio(
-                            &mut audio_pkt_queue,
-                            &mut a_decoder,
-                            &mut resampler,
-                            &mut decoded_audio,
-                            &mut resampled_audio,
-                            None,
-                        );
+                        for track in tracks.iter_mut().filter(|t| t.pending.is_empty()) {
+                            track.decode(None);
+                        }
+                        next_audio_sample = mix_tracks(&mut tracks, is_read_finished)
+                            .map(|samples| tempo.process(&samples))
+                            .filter(|samples| !samples.is_empty());
                     }
                 }
 
@@ -826,7 +885,7 @@ impl VideoDecoder {
                                 break;
                             }
                         };
-                        v_decoder = match open_software_video_decoder(video_parameters.clone()) {
+                        let decoder = match open_software_video_decoder(video_parameters.clone()) {
                             Ok(decoder) => decoder,
                             Err(error) => {
                                 eprintln!(
@@ -835,70 +894,67 @@ impl VideoDecoder {
                                 break;
                             }
                         };
-                        a_decoder = match open_audio_decoder(audio_parameters.clone()) {
-                            Ok(decoder) => decoder,
-                            Err(error) => {
-                                eprintln!(
-                                    "video software fallback failed to open audio decoder: {error}"
-                                );
-                                break;
-                            }
-                        };
-                        resampler_params =
-                            Self::resampler_params_for(&a_decoder, resampler_params.target_rate);
-                        resampler = match Self::create_resampler(
-                            a_decoder.channel_layout(),
-                            device_channel_layout,
-                            &resampler_params,
-                        ) {
-                            Ok(resampler) => resampler,
-                            Err(error) => {
-                                eprintln!(
-                                    "video software fallback failed to create resampler: {error}"
-                                );
-                                break;
-                            }
-                        };
+                        if let Err(error) = tracks
+                            .iter_mut()
+                            .try_for_each(|t| t.reopen(device_channel_layout))
+                        {
+                            eprintln!(
+                                "video software fallback failed to open audio decoder: {error}"
+                            );
+                            break;
+                        }
 
                         hw_selection = None;
                         hardware_pixel_format = None;
-                        w = v_decoder.width();
-                        h = v_decoder.height();
+                        w = decoder.width();
+                        h = decoder.height();
+                        v_decoder = Some(decoder);
                         scaler = None;
                         next_video_frame = None;
                         next_audio_sample = None;
                         video_pkt_queue.clear();
-                        audio_pkt_queue.clear();
                         decoded_frame = Video::empty();
                         hardware_frame = Video::empty();
                         scaled_frame = Video::new(format::Pixel::BGRA, w, h);
-                        decoded_audio = Audio::empty();
-                        resampled_audio = Audio::empty();
                         seek_state = (false, false);
                         is_read_finished = false;
                         unsafe {
                             v_producer.set_write_index(v_producer.read_index());
                             a_producer.set_write_index(a_producer.read_index());
                         }
+                        tempo.reset(playback_rate.load(Ordering::Relaxed) as f64);
                         println!("DEBUG: video decoder: software fallback initialized");
                         continue;
                     }
                 }
 
-                // if ringbuf is full
-                if v_producer.is_full() && a_producer.is_full()
+                // if ringbuf is full, audio ring never fills without a track
+                if v_producer.is_full() && (tracks.is_empty() || a_producer.is_full())
                     || is_read_finished && next_video_frame.is_none() && next_audio_sample.is_none()
                 {
                     thread::sleep(Duration::from_millis(10));
                 }
 
                 // push frame to ringbuf
+                let mut pushed = false;
                 if let Some(f) = next_video_frame.take() {
                     match v_producer.try_push(f) {
-                        Ok(()) => first_video_frame_pushed = true,
+                        Ok(()) => {
+                            first_video_frame_pushed = true;
+                            pushed = true;
+                        }
                         Err(f) => next_video_frame = Some(f),
                     }
                 }
+                // stepped frame is ready, or ringbuf is already full of them
+                if stepping && (pushed || v_producer.is_full()) {
+                    stepping = false;
+                    let mut event = event.lock().unwrap();
+                    // a newer event from the player comes first
+                    if let DecoderEvent::None = *event {
+                        *event = DecoderEvent::Pause;
+                    }
+                }
                 // push audio sample to ringbuf
                 if let Some(s) = next_audio_sample.take() {
                     let written = a_producer.push_slice(&s);
@@ -914,6 +970,49 @@ impl VideoDecoder {
     }
 }
 
+/// empty frames standing in for video of audio only files, the player keeps time by them
+struct Ticks {
+    next: i64,
+    // last tick, none when length of file is unknown
+    end: Option<i64>,
+}
+
+impl Ticks {
+    /// duration in AV_TIME_BASE
+    fn new(duration: i64) -> Self {
+        let end = duration.is_positive().then(|| {
+            (duration as f64 / ffmpeg_next::sys::AV_TIME_BASE as f64 * TICK_RATE as f64).ceil()
+                as i64
+        });
+        Self { next: 0, end }
+    }
+
+    /// next tick, counting from the seek target (in ticks) when given
+    fn frame(&mut self, seek_to: Option<i64>) -> VideoDecodeResult {
+        if let Some(to) = seek_to {
+            self.next = to.max(0);
+        }
+        if self.end.is_some_and(|end| self.next > end) {
+            return VideoDecodeResult::NoFrame;
+        }
+        let frame = FrameImage {
+            image: generate_image_fallback((1, 1), vec![0; 4]),
+            pts: self.next,
+            reseeked: seek_to.is_some(),
+        };
+        self.next += 1;
+        VideoDecodeResult::Frame(frame)
+    }
+}
+
+/// length of a frame at frame rate, 30 fps when stream does not tell
+fn frame_duration(rate: Rational) -> f64 {
+    if rate.numerator() <= 0 || rate.denominator() <= 0 {
+        return 1.0 / 30.0;
+    }
+    rate.denominator() as f64 / rate.numerator() as f64
+}
+
 fn handle_video(
     queue: &mut VecDeque<Packet>,
     decoder: &mut decoder::Video,
@@ -1022,6 +1121,135 @@ fn handle_video(
     VideoDecodeResult::NoFrame
 }
 
+/// one audio stream decoded for playback, tracks are summed when mixing
+struct AudioTrack {
+    ix: usize,
+    gain: f32,
+    parameters: ffmpeg_next::codec::Parameters,
+    time_base: Rational,
+    decoder: decoder::Audio,
+    resampler_params: ResamplerParams,
+    resampler: resampling::context::Context,
+    queue: VecDeque<Packet>,
+    decoded: Audio,
+    resampled: Audio,
+    // resampled samples with gain applied, waiting to be mixed
+    pending: VecDeque<f32>,
+    seeked: bool,
+}
+
+impl AudioTrack {
+    fn open(
+        stream: &format::stream::Stream,
+        gain: f32,
+        target_rate: u32,
+        layout: ChannelLayout,
+    ) -> anyhow::Result<Self> {
+        let parameters = stream.parameters();
+        let decoder = open_audio_decoder(parameters.clone())?;
+        let resampler_params = VideoDecoder::resampler_params_for(&decoder, target_rate);
+        let resampler =
+            VideoDecoder::create_resampler(decoder.channel_layout(), layout, &resampler_params)?;
+        Ok(Self {
+            ix: stream.index(),
+            gain,
+            parameters,
+            time_base: stream.time_base(),
+            decoder,
+            resampler_params,
+            resampler,
+            queue: VecDeque::new(),
+            decoded: Audio::empty(),
+            resampled: Audio::empty(),
+            pending: VecDeque::new(),
+            seeked: false,
+        })
+    }
+
+    /// drop buffered packets and samples after seek
+    fn flush(&mut self, layout: ChannelLayout) -> anyhow::Result<()> {
+        self.decoder.flush();
+        self.queue.clear();
+        self.pending.clear();
+        self.seeked = false;
+        self.resampler = VideoDecoder::create_resampler(
+            self.decoder.channel_layout(),
+            layout,
+            &self.resampler_params,
+        )?;
+        Ok(())
+    }
+
+    /// open decoder again from stream parameters, used by software fallback
+    fn reopen(&mut self, layout: ChannelLayout) -> anyhow::Result<()> {
+        self.decoder = open_audio_decoder(self.parameters.clone())?;
+        self.resampler_params =
+            VideoDecoder::resampler_params_for(&self.decoder, self.resampler_params.target_rate);
+        self.decoded = Audio::empty();
+        self.resampled = Audio::empty();
+        self.flush(layout)
+    }
+
+    /// decode next samples into pending, frames before seek target (in seconds) are dropped
+    fn decode(&mut self, seek_to: Option<f64>) {
+        let target = seek_to.map(|to| {
+            (to * self.time_base.denominator() as f64 / self.time_base.numerator() as f64) as i64
+        });
+        if let Some(samples) = handle_audio(
+            &mut self.queue,
+            &mut self.decoder,
+            &mut self.resampler,
+            &mut self.decoded,
+            &mut self.resampled,
+            target,
+        ) {
+            self.pending
+                .extend(samples.into_iter().map(|sample| sample * self.gain));
+            self.seeked = true;
+        }
+    }
+
+    /// nothing left to play while reading is ahead of this track
+    fn ended(&self, ahead: bool) -> bool {
+        ahead && self.queue.is_empty() && self.pending.is_empty()
+    }
+}
+
+/// reading is done or some track has plenty packets queued,
+/// a track without any packets by then has no audio here
+fn tracks_ahead(tracks: &[AudioTrack], read_finished: bool) -> bool {
+    read_finished || tracks.iter().any(|t| t.queue.len() >= AUDIO_QUEUE_SIZE)
+}
+
+/// sum the samples every playing track has ready
+fn mix_tracks(tracks: &mut [AudioTrack], read_finished: bool) -> Option<Vec<f32>> {
+    let ahead = tracks_ahead(tracks, read_finished);
+    let mut pending: Vec<&mut VecDeque<f32>> = tracks
+        .iter_mut()
+        .filter(|t| !t.ended(ahead))
+        .map(|t| &mut t.pending)
+        .collect();
+    mix_pending(&mut pending)
+}
+
+/// take the samples all queues have and add them up, clipped to full scale
+fn mix_pending(pending: &mut [&mut VecDeque<f32>]) -> Option<Vec<f32>> {
+    let len = pending.iter().map(|p| p.len()).min()?;
+    if len == 0 {
+        return None;
+    }
+    let mut mixed = vec![0.0; len];
+    for queue in pending.iter_mut() {
+        for (m, sample) in mixed.iter_mut().zip(queue.drain(..len)) {
+            *m += sample;
+        }
+    }
+    for m in mixed.iter_mut() {
+        *m = m.clamp(-1.0, 1.0);
+    }
+    Some(mixed)
+}
+
 fn handle_audio(
     queue: &mut VecDeque<Packet>,
     decoder: &mut decoder::Audio,
@@ -1100,6 +1328,18 @@ mod tests {
         assert_eq!(output_channel_layout(6).channels(), 6);
     }
 
+    #[test]
+    fn mix_pending_waits_for_slowest_track_and_clips() {
+        let mut a = VecDeque::from(vec![0.5, 0.8, 0.1]);
+        let mut b = VecDeque::from(vec![0.25, 0.5]);
+        let mixed = mix_pending(&mut [&mut a, &mut b]).unwrap();
+
+        assert_eq!(mixed, vec![0.75, 1.0]);
+        assert_eq!(a, VecDeque::from(vec![0.1]));
+        assert!(b.is_empty());
+        assert!(mix_pending(&mut [&mut a, &mut b]).is_none());
+    }
+
     #[test]
     fn gpu_policy_changes_hardware_candidate_priority() {
         assert!(hardware_enabled(GpuPolicy::PreferIntegrated));
@@ -1125,4 +1365,25 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn frame_duration_falls_back_without_rate() {
+        assert_eq!(frame_duration(Rational::new(25, 1)), 0.04);
+        assert_eq!(frame_duration(Rational::new(30000, 1001)), 1001.0 / 30000.0);
+        assert_eq!(frame_duration(Rational::new(0, 1)), 1.0 / 30.0);
+    }
+
+    #[test]
+    fn ticks_follow_seek_and_stop_at_end() {
+        // one second, 25 ticks
+        let mut ticks = Ticks::new(ffmpeg_next::sys::AV_TIME_BASE as i64);
+        let pts = |result: VideoDecodeResult| match result {
+            VideoDecodeResult::Frame(f) => Some((f.pts, f.reseeked)),
+            _ => None,
+        };
+        assert_eq!(pts(ticks.frame(None)), Some((0, false)));
+        assert_eq!(pts(ticks.frame(Some(24))), Some((24, true)));
+        assert_eq!(pts(ticks.frame(None)), Some((25, false)));
+        assert_eq!(pts(ticks.frame(None)), None);
+    }
 }
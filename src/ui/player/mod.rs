@@ -4,6 +4,10 @@ pub mod model;
 pub mod player;
 pub mod settings;
 pub mod size;
+pub mod tempo;
+pub mod thumbnail;
 pub mod timer;
 pub mod utils;
 pub mod viewer;
+pub mod wave_viewer;
+pub mod waveform;
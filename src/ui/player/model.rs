@@ -2,6 +2,7 @@ use std::sync::Arc;
 
 use gpui::{RenderImage, SharedString};
 use gpui_component::select::SelectItem;
+use serde::{Deserialize, Serialize};
 
 pub struct FrameImage {
     pub image: Arc<RenderImage>,
@@ -25,6 +26,68 @@ pub struct AudioRail {
     pub handler_name: Option<SharedString>,
 }
 
+/// kind of a stream which is neither video nor audio
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamKind {
+    Subtitle,
+    Attachment,
+    Data,
+}
+
+impl StreamKind {
+    pub fn from_medium(medium: ffmpeg_next::media::Type) -> Option<Self> {
+        match medium {
+            ffmpeg_next::media::Type::Subtitle => Some(Self::Subtitle),
+            ffmpeg_next::media::Type::Attachment => Some(Self::Attachment),
+            ffmpeg_next::media::Type::Data => Some(Self::Data),
+            _ => None,
+        }
+    }
+
+    pub const fn i18n_key(self) -> &'static str {
+        match self {
+            Self::Subtitle => "output.streams.subtitle",
+            Self::Attachment => "output.streams.attachment",
+            Self::Data => "output.streams.data",
+        }
+    }
+}
+
+/// subtitle, attachment or data stream which can be copied into export
+#[derive(Debug, Clone)]
+pub struct ExtraStream {
+    pub ix: usize,
+    pub kind: StreamKind,
+    pub codec: SharedString,
+    // language, title or file name of attachment
+    pub name: Option<SharedString>,
+}
+
+impl ExtraStream {
+    pub fn from_stream(stream: &ffmpeg_next::format::stream::Stream) -> Option<Self> {
+        let parameters = stream.parameters();
+        let kind = StreamKind::from_medium(parameters.medium())?;
+        let metadata = stream.metadata();
+        let name = ["filename", "title", "language"]
+            .iter()
+            .find_map(|key| metadata.get(key))
+            .map(|s| SharedString::from(s.to_string()));
+        Some(Self {
+            ix: stream.index(),
+            kind,
+            codec: parameters.id().name().into(),
+            name,
+        })
+    }
+}
+
+/// audio rail mixed into playback and export, with its gain
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MixTrack {
+    pub ix: usize,
+    pub gain: f32,
+}
+
 impl SelectItem for AudioRail {
     type Value = usize;
 
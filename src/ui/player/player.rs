@@ -1,12 +1,13 @@
 use std::{
+    collections::VecDeque,
     f64,
     path::PathBuf,
     sync::{
         Arc,
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
     },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use atomic_float::AtomicF32;
@@ -22,10 +23,10 @@ use crate::{
     ui::{
         player::{
             audio::AudioPlayer,
-            decoder::{DecoderEvent, VideoDecoder},
-            model::{FrameAction, FrameImage},
+            decoder::{DecoderEvent, STEP_CACHE_FRAMES, VideoDecoder},
+            model::{FrameAction, FrameImage, MixTrack},
             size::PlayerSize,
-            timer::Timer,
+            timer::{AudioClock, Timer},
             utils::generate_image_fallback,
             viewer::Viewer,
         },
@@ -40,6 +41,18 @@ pub enum PlayState {
     Stopped,
 }
 
+/// playback rates stepped through by shuttle keys
+pub static PLAYBACK_RATES: [f32; 8] = [0.25, 0.5, 0.75, 1.0, 1.5, 2.0, 3.0, 4.0];
+
+/// shortest time between two seeks sent while scrubbing
+static SCRUB_INTERVAL: Duration = Duration::from_millis(80);
+
+/// decoded frame kept for stepping
+struct StepFrame {
+    time: f64,
+    image: Arc<RenderImage>,
+}
+
 pub struct Player {
     init: bool,
     timer: Timer,
@@ -57,8 +70,24 @@ pub struct Player {
 
     recent_pts: f64,
     pending_seeking: Option<f64>,
+    // scrub position not sent to decoder yet
+    scrub_target: Option<f64>,
+    last_scrub: Option<Instant>,
+    // time of frame on screen
+    shown_time: f64,
+    // frames before and after the shown one while stepping, nearest last
+    step_back: VecDeque<StepFrame>,
+    step_ahead: Vec<StepFrame>,
+    // stepping back waits for decoder to reach the frame on screen again
+    step_until: Option<f64>,
+    // decoder and audio are not at the shown frame after stepping
+    stepped: bool,
     play_signal: Arc<AtomicBool>,
     audio_gain: Arc<AtomicF32>,
+    playback_rate: Arc<AtomicF32>,
+    // playback follows audio, timer is used without it and while paused
+    audio_clock: AudioClock,
+    has_audio: bool,
 }
 
 impl Player {
@@ -72,9 +101,20 @@ impl Player {
 
         let play_signal = Arc::new(AtomicBool::new(false));
         let audio_gain = Arc::new(AtomicF32::new(0.5));
+        let consumed = Arc::new(AtomicU64::new(0));
         audio_player
-            .spawn(a_consumer, play_signal.clone(), audio_gain.clone())
+            .spawn(
+                a_consumer,
+                play_signal.clone(),
+                audio_gain.clone(),
+                consumed.clone(),
+            )
             .expect("failed to create audio output stream");
+        let audio_clock = AudioClock::new(
+            consumed,
+            audio_player.sample_rate(),
+            audio_player.channels(),
+        );
 
         Self {
             init: false,
@@ -93,8 +133,18 @@ impl Player {
 
             recent_pts: 0.0,
             pending_seeking: None,
+            scrub_target: None,
+            last_scrub: None,
+            shown_time: 0.0,
+            step_back: VecDeque::new(),
+            step_ahead: vec![],
+            step_until: None,
+            stepped: false,
             play_signal,
             audio_gain,
+            playback_rate: Arc::new(AtomicF32::new(1.0)),
+            audio_clock,
+            has_audio: false,
         }
     }
 
@@ -125,7 +175,7 @@ impl Player {
         Some((self.current_playtime() / d_sec as f64) as f32)
     }
 
-    /// open video file, this will set the init state as true
+    /// open video file, the init state is set once it opened
     pub fn open<T>(&mut self, cx: &mut Context<T>, path: &PathBuf) -> anyhow::Result<()>
     where
         T: 'static,
@@ -138,32 +188,39 @@ impl Player {
             self.audio_player.sample_rate(),
             self.audio_player.channels(),
         );
-        match decoder {
-            Ok(d) => {
-                let d = d
-                    .set_video_producer(self.producer.take().unwrap())
-                    .set_audio_producer(self.a_producer.take().unwrap());
-                self.decoder = Some(d);
-            }
-            Err(e) => println!("error: {}", e),
-        }
+        let d = decoder?
+            .set_video_producer(self.producer.take().unwrap())
+            .set_audio_producer(self.a_producer.take().unwrap())
+            .set_playback_rate(self.playback_rate.clone());
+        self.decoder = Some(d);
+        self.has_audio = self.output_params.read(cx).audio_stream_ix.is_some();
         self.init = true;
         Ok(())
     }
 
-    /// spawn decoder and start play
-    pub fn start_play(&mut self, cx: &mut Context<MyApp>, audio_ix: Option<usize>) {
+    /// spawn decoder and start play, rails in mix are played together
+    pub fn start_play(
+        &mut self,
+        cx: &mut Context<MyApp>,
+        audio_ix: Option<usize>,
+        mix: &[MixTrack],
+    ) {
         if let Some(decoder) = self.decoder.as_mut() {
-            decoder.spawn_decoder(self.size.clone(), cx, audio_ix);
+            decoder.spawn_decoder(self.size.clone(), cx, audio_ix, mix);
             self.state = PlayState::Playing;
-            self.timer.start();
+            self.start_clock();
         }
     }
 
     /// player control method
     pub fn resume_play(&mut self) {
         self.state = PlayState::Playing;
-        self.timer.start();
+        if self.stepped {
+            // play from the stepped frame, decoder is elsewhere
+            self.seek_to(self.shown_time);
+            return;
+        }
+        self.start_clock();
         self.audio_player.play().unwrap();
         if let Some(decoder) = self.decoder.as_mut() {
             decoder.set_event(DecoderEvent::None);
@@ -175,7 +232,7 @@ impl Player {
         if let Some(decoder) = self.decoder.as_mut() {
             decoder.set_event(DecoderEvent::Pause);
             self.state = PlayState::Paused;
-            self.timer.stop();
+            self.stop_clock();
             self.audio_player.pause().unwrap();
         }
     }
@@ -193,13 +250,14 @@ impl Player {
 
     /// find and seek to last key frame
     pub fn last_key(&mut self) {
-        self.timer.stop();
+        self.stop_clock();
         let ct = self.current_playtime();
         let target = (ct - 0.1).max(0.0);
 
         if let Some(d) = self.decoder.as_mut() {
             d.set_event(DecoderEvent::LastKey(target));
         }
+        self.clear_steps();
 
         self.pending_seeking = Some(target);
         self.frame_buf = None;
@@ -209,13 +267,14 @@ impl Player {
 
     /// find and seek to next key frame
     pub fn next_key(&mut self) {
-        self.timer.stop();
+        self.stop_clock();
         let ct = self.current_playtime();
         let target = ct + 0.1;
 
         if let Some(d) = self.decoder.as_mut() {
             d.set_event(DecoderEvent::NextKey(target));
         }
+        self.clear_steps();
         self.pending_seeking = Some(target);
         self.frame_buf = None;
         self.consumer.clear();
@@ -231,24 +290,204 @@ impl Player {
             let dur_sec = self.duration_sec().unwrap_or(0.);
             let now = match self.pending_seeking {
                 Some(t) => t,
-                None => self.timer.current_time_sec(),
+                None => self.clock_time(),
             };
 
             self.seek_to(update_fn(now, dur_sec).clamp(0.0, dur_sec));
         }
     }
 
+    /// seek while playhead is dragged, decoder gets one seek per interval at most
+    pub fn scrub_to(&mut self, time: f64) {
+        if self.state == PlayState::Stopped {
+            return;
+        }
+        let time = time.clamp(0.0, self.duration_sec().unwrap_or(0.));
+        if self
+            .last_scrub
+            .is_some_and(|t| t.elapsed() < SCRUB_INTERVAL)
+        {
+            self.scrub_target = Some(time);
+            return;
+        }
+        self.scrub_target = None;
+        self.last_scrub = Some(Instant::now());
+        self.seek_to(time);
+    }
+
+    /// send the last scrub position held back
+    pub fn finish_scrub(&mut self) {
+        self.last_scrub = None;
+        if let Some(time) = self.scrub_target.take() {
+            self.seek_to(time);
+        }
+    }
+
     /// seek player with sec
     pub fn seek_to(&mut self, time: f64) {
         self.pending_seeking = Some(time);
         if let Some(decoder) = self.decoder.as_mut() {
             decoder.set_event(DecoderEvent::Seek(time));
         };
+        self.audio_clock.stop();
+        self.clear_steps();
         self.frame_buf = None;
         self.consumer.clear();
         self.audio_player.pause().unwrap();
     }
 
+    /// show the next frame, pauses playing
+    pub fn step_forward(&mut self, w: &mut Window) {
+        if !self.prepare_step() {
+            return;
+        }
+        if let Some(d) = self.decoder.as_mut() {
+            d.set_event(DecoderEvent::Step);
+        }
+        let next = match self.step_ahead.pop() {
+            Some(frame) => frame,
+            None => match self.next_decoded() {
+                Some(frame) => frame,
+                None => return,
+            },
+        };
+        let shown = self.show_step(w, next);
+        self.push_step_back(shown);
+    }
+
+    /// show the frame before, decoded again from keyframe when not kept
+    pub fn step_back(&mut self, w: &mut Window) {
+        if !self.prepare_step() {
+            return;
+        }
+        if let Some(prev) = self.step_back.pop_back() {
+            let shown = self.show_step(w, prev);
+            self.step_ahead.push(shown);
+            return;
+        }
+        self.step_ahead.clear();
+        self.step_until = Some(self.shown_time);
+        self.pending_seeking = Some(self.shown_time);
+        self.frame_buf = None;
+        self.consumer.clear();
+        self.stepped = true;
+        if let Some(d) = self.decoder.as_mut() {
+            d.set_event(DecoderEvent::StepBack(self.shown_time));
+        }
+    }
+
+    /// pause for stepping, false while a seek or step is on the way
+    fn prepare_step(&mut self) -> bool {
+        if self.state == PlayState::Playing {
+            self.pause_play();
+        }
+        self.state == PlayState::Paused && !self.is_seeking() && self.step_until.is_none()
+    }
+
+    /// put a frame on screen while stepping, returns the one it replaced
+    fn show_step(&mut self, w: &mut Window, frame: StepFrame) -> StepFrame {
+        w.drop_image(self.frame.clone()).unwrap();
+        let shown = StepFrame {
+            time: self.shown_time,
+            image: std::mem::replace(&mut self.frame, frame.image),
+        };
+        self.shown_time = frame.time;
+        self.timer.set_time_sec(frame.time);
+        self.stepped = true;
+        shown
+    }
+
+    fn push_step_back(&mut self, frame: StepFrame) {
+        self.step_back.push_back(frame);
+        if self.step_back.len() > STEP_CACHE_FRAMES {
+            self.step_back.pop_front();
+        }
+    }
+
+    /// next decoded frame after the shown one, older ones are dropped
+    fn next_decoded(&mut self) -> Option<StepFrame> {
+        let half_frame = self.frame_duration() / 2.0;
+        while let Some(frame) = self.frame_buf.take().or_else(|| self.consumer.try_pop()) {
+            let Some(time) = self.frame_time(frame.pts) else {
+                continue;
+            };
+            if time > self.shown_time + half_frame {
+                return Some(StepFrame {
+                    time,
+                    image: frame.image,
+                });
+            }
+        }
+        None
+    }
+
+    /// keep frames decoded before the one stepped back from, then show the last of them
+    fn collect_step_back(&mut self, w: &mut Window) {
+        let Some(until) = self.step_until else {
+            return;
+        };
+        let half_frame = self.frame_duration() / 2.0;
+        while let Some(frame) = self.consumer.try_pop() {
+            if self.is_seeking() {
+                // frames decoded before the seek
+                if !frame.reseeked {
+                    continue;
+                }
+                self.pending_seeking = None;
+            }
+            let Some(time) = self.frame_time(frame.pts) else {
+                continue;
+            };
+            if time < until - half_frame {
+                self.push_step_back(StepFrame {
+                    time,
+                    image: frame.image,
+                });
+                continue;
+            }
+            // back at the shown frame, later ones stay for stepping forward
+            self.frame_buf = Some(frame);
+            self.step_until = None;
+            if let Some(prev) = self.step_back.pop_back() {
+                let shown = self.show_step(w, prev);
+                self.step_ahead.push(shown);
+            }
+            return;
+        }
+    }
+
+    fn clear_steps(&mut self) {
+        self.step_back.clear();
+        self.step_ahead.clear();
+        self.step_until = None;
+        self.stepped = false;
+    }
+
+    fn frame_duration(&self) -> f64 {
+        self.decoder
+            .as_ref()
+            .map(|d| d.frame_duration())
+            .unwrap_or(1.0 / 30.0)
+    }
+
+    pub fn get_rate(&self) -> f32 {
+        self.playback_rate.load(Ordering::Relaxed)
+    }
+
+    /// change playback rate, buffered audio is decoded again at the new rate
+    pub fn set_rate(&mut self, rate: f32) {
+        let rate = rate.clamp(PLAYBACK_RATES[0], PLAYBACK_RATES[PLAYBACK_RATES.len() - 1]);
+        if rate == self.get_rate() {
+            return;
+        }
+        let now = self.current_playtime();
+        self.playback_rate.store(rate, Ordering::Relaxed);
+        self.timer.set_rate(rate as f64);
+        if self.state != PlayState::Stopped {
+            self.seek_to(now);
+        }
+    }
+
     pub fn get_gain(&self) -> f32 {
         self.audio_gain.load(Ordering::Relaxed)
     }
@@ -273,10 +512,33 @@ impl Player {
 
     /// calc current time
     pub fn current_playtime(&self) -> f64 {
-        if let Some(seek) = self.pending_seeking {
+        if let Some(seek) = self.scrub_target.or(self.pending_seeking) {
             return seek;
         }
-        self.timer.current_time_sec()
+        self.clock_time()
+    }
+
+    /// time of audio the device played, timer when there is no audio or while paused
+    fn clock_time(&self) -> f64 {
+        self.audio_clock
+            .time()
+            .unwrap_or_else(|| self.timer.current_time_sec())
+    }
+
+    /// run clock on from time of timer
+    fn start_clock(&mut self) {
+        if self.has_audio {
+            self.audio_clock
+                .start(self.timer.current_time_sec(), self.get_rate() as f64);
+        }
+        self.timer.start();
+    }
+
+    /// hold clock at current time
+    fn stop_clock(&mut self) {
+        let now = self.clock_time();
+        self.audio_clock.stop();
+        self.timer.set_time_sec(now);
     }
 
     /// block and wait next audio callback signal
@@ -315,10 +577,10 @@ impl Player {
                 self.pending_seeking = None;
                 // reset timer
                 self.timer.set_time_sec(self.frame_time(frame.pts).unwrap());
-                // resume play if need
+                // resume play if need, clock counts audio from here
                 if self.state == PlayState::Playing {
+                    self.start_clock();
                     self.audio_play_block();
-                    self.timer.start();
                 }
                 return FrameAction::Render;
             } else {
@@ -328,7 +590,11 @@ impl Player {
         }
 
         let play_time = self.current_playtime();
-        if frame_time > play_time + 0.3 {
+        let rate = self.get_rate() as f64;
+        if rate > 1.0 && frame_time < play_time - self.frame_duration() * rate {
+            // screen can not keep up with every frame at high rate
+            FrameAction::Drop
+        } else if frame_time > play_time + 0.3 {
             // frame is too future, wait
             FrameAction::Wait
         } else if frame_time < play_time - 0.3 {
@@ -345,35 +611,50 @@ impl Player {
 
     /// build new viewer for every frame
     pub fn view(&mut self, w: &mut Window) -> Viewer {
-        // only keep flash when playing and seeking
-        if self.state == PlayState::Playing || self.is_seeking() {
+        // mouse stopped while scrubbing
+        if let Some(time) = self.scrub_target
+            && self
+                .last_scrub
+                .is_some_and(|t| t.elapsed() >= SCRUB_INTERVAL)
+        {
+            self.scrub_to(time);
+        }
+        if self.step_until.is_some() {
+            self.collect_step_back(w);
+        } else if self.state == PlayState::Playing || self.is_seeking() {
+            // only keep flash when playing and seeking
             // on the end of play
-            if self.timer.current_time_sec() >= self.duration_sec().unwrap_or(0.0)
-                && !self.is_seeking()
-            {
+            if self.clock_time() >= self.duration_sec().unwrap_or(0.0) && !self.is_seeking() {
                 self.pause_play();
                 self.seek_to(0.0);
             }
-            // prepare next frame from buf or decoder
-            let next_frame: Option<FrameImage>;
-            if let Some(fb) = self.frame_buf.take() {
-                // if buffer is not none, clear it first
-                next_frame = Some(fb);
-            } else if let Some(f) = self.consumer.try_pop() {
-                next_frame = Some(f);
-            } else {
-                next_frame = None;
-            }
+            // dropped frames are skipped within one view, to keep up at high rate
+            loop {
+                // prepare next frame from buf or decoder
+                let next_frame: Option<FrameImage>;
+                if let Some(fb) = self.frame_buf.take() {
+                    // if buffer is not none, clear it first
+                    next_frame = Some(fb);
+                } else if let Some(f) = self.consumer.try_pop() {
+                    next_frame = Some(f);
+                } else {
+                    next_frame = None;
+                }
 
-            // render the frame by conpare result
-            if let Some(next_frame) = next_frame {
+                // render the frame by conpare result
+                let Some(next_frame) = next_frame else {
+                    break;
+                };
                 match self.compare_time(&next_frame) {
                     FrameAction::Wait => {
                         self.frame_buf = Some(next_frame);
+                        break;
                     }
                     FrameAction::Render => {
                         w.drop_image(self.frame.clone()).unwrap();
                         self.frame = next_frame.image;
+                        self.shown_time = self.recent_pts;
+                        break;
                     }
                     FrameAction::Drop => {
                         w.drop_image(next_frame.image).unwrap();
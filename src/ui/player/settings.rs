@@ -1,8 +1,10 @@
-use crate::ui::player::model::AudioRail;
+use crate::ui::player::model::{AudioRail, MixTrack};
 
 pub struct PlayerSettings {
     pub audio_ix: usize,
     pub audio_rails: Vec<AudioRail>,
+    // rails played together, only audio_ix is played when empty
+    pub mix: Vec<MixTrack>,
 }
 
 impl PlayerSettings {
@@ -10,6 +12,7 @@ impl PlayerSettings {
         Self {
             audio_ix: 0,
             audio_rails: vec![],
+            mix: vec![],
         }
     }
 }
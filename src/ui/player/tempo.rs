@@ -0,0 +1,127 @@
+/// length of segments overlapped into each other
+static OVERLAP_SEC: f64 = 0.02;
+/// how far a segment may move to line up with the one before
+static SEARCH_SEC: f64 = 0.008;
+/// frames skipped while comparing segments, enough to find the best offset
+static SIMILARITY_STRIDE: usize = 4;
+
+/// changes tempo of interleaved samples keeping pitch, by waveform similarity overlap-add
+pub struct Tempo {
+    channels: usize,
+    overlap: usize,
+    search: usize,
+    speed: f64,
+    // interleaved samples not consumed yet
+    input: Vec<f32>,
+    // frame of input where the next segment is taken around
+    position: f64,
+    // continuation of last segment, faded into the next one
+    tail: Vec<f32>,
+}
+
+impl Tempo {
+    pub fn new(sample_rate: u32, channels: u16) -> Self {
+        Self {
+            channels: channels.max(1) as usize,
+            overlap: ((sample_rate as f64 * OVERLAP_SEC) as usize).max(1),
+            search: (sample_rate as f64 * SEARCH_SEC) as usize,
+            speed: 1.0,
+            input: vec![],
+            position: 0.0,
+            tail: vec![],
+        }
+    }
+
+    /// drop buffered samples and take new speed, after seek
+    pub fn reset(&mut self, speed: f64) {
+        self.speed = speed;
+        self.input.clear();
+        self.position = 0.0;
+        self.tail.clear();
+    }
+
+    /// stretched samples, some input is held back until the next call
+    pub fn process(&mut self, samples: &[f32]) -> Vec<f32> {
+        if self.speed == 1.0 {
+            return samples.to_vec();
+        }
+        self.input.extend_from_slice(samples);
+
+        let ch = self.channels;
+        let frames = self.input.len() / ch;
+        let mut output = vec![];
+        loop {
+            let position = self.position.round() as usize;
+            if position + self.search + 2 * self.overlap > frames {
+                break;
+            }
+            let start = if self.tail.is_empty() {
+                position
+            } else {
+                self.best_start(position)
+            };
+            let segment = &self.input[start * ch..(start + self.overlap) * ch];
+            if self.tail.is_empty() {
+                output.extend_from_slice(segment);
+            } else {
+                for (i, (tail, sample)) in self.tail.iter().zip(segment).enumerate() {
+                    let fade = ((i / ch) as f32 + 0.5) / self.overlap as f32;
+                    output.push(tail * (1.0 - fade) + sample * fade);
+                }
+            }
+            self.tail =
+                self.input[(start + self.overlap) * ch..(start + 2 * self.overlap) * ch].to_vec();
+            self.position += self.overlap as f64 * self.speed;
+        }
+
+        // input before search range of the next segment is not needed anymore
+        let used = (self.position as usize).saturating_sub(self.search);
+        if used > 0 {
+            self.input.drain(..used * ch);
+            self.position -= used as f64;
+        }
+        output
+    }
+
+    /// start of segment around position which continues the tail best
+    fn best_start(&self, position: usize) -> usize {
+        (position.saturating_sub(self.search)..=position + self.search)
+            .map(|start| (start, self.similarity(start)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map_or(position, |(start, _)| start)
+    }
+
+    /// normalized correlation of tail and segment at start
+    fn similarity(&self, start: usize) -> f32 {
+        let ch = self.channels;
+        let segment = &self.input[start * ch..(start + self.overlap) * ch];
+        let (mut product, mut energy) = (0.0, 0.0);
+        for frame in (0..self.overlap).step_by(SIMILARITY_STRIDE) {
+            for c in 0..ch {
+                let sample = segment[frame * ch + c];
+                product += self.tail[frame * ch + c] * sample;
+                energy += sample * sample;
+            }
+        }
+        product / (energy.sqrt() + f32::EPSILON)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_length_follows_speed() {
+        let mut tempo = Tempo::new(8000, 2);
+        tempo.reset(2.0);
+        let input: Vec<f32> = (0..160_000)
+            .map(|i| ((i / 2) as f32 * 0.05).sin())
+            .collect();
+        let output: usize = input.chunks(2000).map(|c| tempo.process(c).len()).sum();
+        assert!((output as f64 - 80_000.0).abs() < 2_000.0);
+
+        tempo.reset(1.0);
+        assert_eq!(tempo.process(&[0.5, 0.25]), vec![0.5, 0.25]);
+    }
+}
@@ -0,0 +1,364 @@
+use std::{
+    collections::{BTreeMap, HashSet, VecDeque},
+    fs::{self, File, OpenOptions},
+    io::{BufReader, Read, Write},
+    ops::Range,
+    path::PathBuf,
+    sync::Arc,
+    thread,
+};
+
+use anyhow::{anyhow, bail};
+use async_channel::{Receiver, Sender};
+use ffmpeg_next::{
+    codec, decoder,
+    format::{self, Pixel},
+    frame::Video,
+    software::scaling,
+};
+use gpui::RenderImage;
+
+use crate::ui::player::utils::{generate_image_fallback, source_cache_path};
+
+/// height of filmstrip thumbnails in pixels
+pub static THUMB_HEIGHT: u32 = 36;
+/// thumbnails across the whole file when zoomed out
+static BASE_SLOTS: f64 = 32.;
+/// thumbnails asked for the visible part of timeline
+static VISIBLE_SLOTS: usize = 32;
+/// closest thumbnails get when zooming in
+static MIN_INTERVAL_SEC: f64 = 0.5;
+/// widest thumbnail, wider records in cache are taken as corrupt
+static MAX_THUMB_WIDTH: u32 = 1024;
+
+/// scaled down frame of source
+#[derive(Clone)]
+pub struct Thumbnail {
+    pub time: f64,
+    pub image: Arc<RenderImage>,
+}
+
+/// thumbnail in BGRA, as stored in cache file
+struct RawThumbnail {
+    time_ms: u64,
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl RawThumbnail {
+    fn into_thumbnail(self) -> Thumbnail {
+        Thumbnail {
+            time: self.time_ms as f64 / 1000.,
+            image: generate_image_fallback((self.width, self.height), self.pixels),
+        }
+    }
+
+    fn write(&self, file: &mut File) -> std::io::Result<()> {
+        file.write_all(&self.time_ms.to_le_bytes())?;
+        file.write_all(&self.width.to_le_bytes())?;
+        file.write_all(&self.height.to_le_bytes())?;
+        file.write_all(&self.pixels)
+    }
+
+    /// next record of cache, none at the end or on a cut off or corrupt record
+    fn read(reader: &mut impl Read) -> Option<Self> {
+        let mut time_ms = [0u8; 8];
+        let mut width = [0u8; 4];
+        let mut height = [0u8; 4];
+        reader.read_exact(&mut time_ms).ok()?;
+        reader.read_exact(&mut width).ok()?;
+        reader.read_exact(&mut height).ok()?;
+        let (width, height) = (u32::from_le_bytes(width), u32::from_le_bytes(height));
+        if width == 0 || width > MAX_THUMB_WIDTH || height != THUMB_HEIGHT {
+            return None;
+        }
+        let len = width.checked_mul(height)?.checked_mul(4)?;
+        let mut pixels = vec![0u8; len as usize];
+        reader.read_exact(&mut pixels).ok()?;
+        Some(Self {
+            time_ms: u64::from_le_bytes(time_ms),
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+/// extracts thumbnails of one file in background, stops when dropped
+pub struct ThumbnailExtractor {
+    requests: Sender<Vec<f64>>,
+}
+
+impl ThumbnailExtractor {
+    /// cached thumbnails come out of the receiver first, then extracted ones
+    pub fn spawn(path: &PathBuf, video_ix: usize) -> (Self, Receiver<Thumbnail>) {
+        let (requests, request_rx) = async_channel::unbounded();
+        let (thumbnail_tx, thumbnails) = async_channel::unbounded();
+        let path = path.clone();
+        thread::spawn(move || {
+            if let Err(e) = run_extractor(&path, video_ix, request_rx, thumbnail_tx) {
+                eprintln!("failed to extract thumbnails of {:?}: {}", path, e);
+            }
+        });
+        (Self { requests }, thumbnails)
+    }
+
+    /// replace pending requests, times already extracted are skipped
+    pub fn request(&self, times: Vec<f64>) {
+        let _ = self.requests.try_send(times);
+    }
+}
+
+fn run_extractor(
+    path: &PathBuf,
+    video_ix: usize,
+    requests: Receiver<Vec<f64>>,
+    thumbnails: Sender<Thumbnail>,
+) -> anyhow::Result<()> {
+    let cache_path = source_cache_path(path, "thumbnails", &format!("{}.bin", THUMB_HEIGHT));
+    let mut done = HashSet::new();
+    if let Some(file) = cache_path.as_ref().and_then(|p| File::open(p).ok()) {
+        let mut reader = BufReader::new(file);
+        while let Some(raw) = RawThumbnail::read(&mut reader) {
+            done.insert(raw.time_ms);
+            if thumbnails.send_blocking(raw.into_thumbnail()).is_err() {
+                return Ok(());
+            }
+        }
+    }
+    let mut cache = cache_path.and_then(|p| {
+        fs::create_dir_all(p.parent()?).ok()?;
+        OpenOptions::new().create(true).append(true).open(p).ok()
+    });
+
+    let mut input = format::input(path)?;
+    let stream = input
+        .stream(video_ix)
+        .ok_or(anyhow!("failed to get target stream"))?;
+    let mut decoder = codec::context::Context::from_parameters(stream.parameters())?
+        .decoder()
+        .video()?;
+    let mut scaler = None;
+
+    let mut pending = VecDeque::new();
+    loop {
+        if requests.is_closed() {
+            return Ok(());
+        }
+        // block when idle, newest request replaces older ones
+        if pending.is_empty() {
+            match requests.recv_blocking() {
+                Ok(times) => pending = times.into(),
+                Err(_) => return Ok(()),
+            }
+        }
+        while let Ok(times) = requests.try_recv() {
+            pending = times.into();
+        }
+        let Some(time) = pending.pop_front() else {
+            continue;
+        };
+        let time_ms = (time * 1000.).round() as u64;
+        if !done.insert(time_ms) {
+            continue;
+        }
+
+        let raw = match extract(&mut input, &mut decoder, &mut scaler, video_ix, time) {
+            Ok(pixels) => RawThumbnail { time_ms, ..pixels },
+            Err(e) => {
+                eprintln!("failed to extract thumbnail at {}: {}", time, e);
+                continue;
+            }
+        };
+        if let Some(file) = cache.as_mut()
+            && let Err(e) = raw.write(file)
+        {
+            eprintln!("failed to write thumbnail cache: {}", e);
+            cache = None;
+        }
+        if thumbnails.send_blocking(raw.into_thumbnail()).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+/// keyframe at or before time, scaled to thumbnail height
+fn extract(
+    input: &mut format::context::Input,
+    decoder: &mut decoder::Video,
+    scaler: &mut Option<scaling::Context>,
+    video_ix: usize,
+    time: f64,
+) -> anyhow::Result<RawThumbnail> {
+    let ts = (ffmpeg_next::sys::AV_TIME_BASE as f64 * time) as i64;
+    input.seek(ts, ..ts)?;
+    decoder.flush();
+
+    let mut decoded = Video::empty();
+    let mut found = false;
+    for (stream, packet) in input.packets() {
+        if stream.index() != video_ix {
+            continue;
+        }
+        decoder.send_packet(&packet)?;
+        if decoder.receive_frame(&mut decoded).is_ok() {
+            found = true;
+            break;
+        }
+    }
+    if !found {
+        bail!("no frame decoded");
+    }
+
+    let (width, height) = thumb_size(decoded.width(), decoded.height());
+    // built again when stream changes size or format midway
+    let input = (decoded.format(), decoded.width(), decoded.height());
+    if scaler
+        .as_ref()
+        .is_none_or(|s| (s.input().format, s.input().width, s.input().height) != input)
+    {
+        *scaler = Some(scaling::Context::get(
+            decoded.format(),
+            decoded.width(),
+            decoded.height(),
+            Pixel::BGRA,
+            width,
+            height,
+            scaling::Flags::BILINEAR,
+        )?);
+    }
+    let mut scaled = Video::empty();
+    scaler.as_mut().unwrap().run(&decoded, &mut scaled)?;
+
+    let data = scaled.data(0);
+    let stride = scaled.stride(0);
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height as usize {
+        let start = y * stride;
+        pixels.extend_from_slice(&data[start..start + width as usize * 4]);
+    }
+    Ok(RawThumbnail {
+        time_ms: 0,
+        width,
+        height,
+        pixels,
+    })
+}
+
+fn thumb_size(width: u32, height: u32) -> (u32, u32) {
+    let thumb_width = (width as f64 * THUMB_HEIGHT as f64 / height.max(1) as f64).round() as u32;
+    (thumb_width.clamp(1, MAX_THUMB_WIDTH), THUMB_HEIGHT)
+}
+
+/// times of thumbnails for the visible span, the grid halves per zoom level
+/// so thumbnails of a lower zoom are reused
+fn grid_times(duration: f64, visible: &Range<f64>, slots: usize) -> Vec<f64> {
+    if duration <= 0. || slots == 0 {
+        return vec![];
+    }
+    let wanted = (visible.end - visible.start) / slots as f64;
+    let mut interval = duration / BASE_SLOTS;
+    while interval / 2. >= wanted && interval / 2. >= MIN_INTERVAL_SEC {
+        interval /= 2.;
+    }
+    let first = (visible.start / interval).floor() as i64;
+    let last = (visible.end / interval).ceil() as i64;
+    (first..=last)
+        .map(|i| i as f64 * interval)
+        .filter(|t| *t >= 0. && *t < duration)
+        .collect()
+}
+
+/// thumbnails of the open file, filled by the extractor
+pub struct Filmstrip {
+    extractor: ThumbnailExtractor,
+    // by time in ms
+    thumbnails: BTreeMap<u64, Arc<RenderImage>>,
+    requested: Vec<f64>,
+}
+
+impl Filmstrip {
+    pub fn open(path: &PathBuf, video_ix: usize) -> (Self, Receiver<Thumbnail>) {
+        let (extractor, thumbnails) = ThumbnailExtractor::spawn(path, video_ix);
+        (
+            Self {
+                extractor,
+                thumbnails: BTreeMap::new(),
+                requested: vec![],
+            },
+            thumbnails,
+        )
+    }
+
+    pub fn insert(&mut self, thumbnail: Thumbnail) {
+        let time_ms = (thumbnail.time * 1000.).round() as u64;
+        self.thumbnails.insert(time_ms, thumbnail.image);
+    }
+
+    /// ask for the missing thumbnails of visible span, in seconds
+    pub fn request(&mut self, duration: f64, visible: Range<f64>) {
+        let times = grid_times(duration, &visible, VISIBLE_SLOTS);
+        if times == self.requested {
+            return;
+        }
+        let missing = times
+            .iter()
+            .copied()
+            .filter(|t| !self.thumbnails.contains_key(&((t * 1000.).round() as u64)))
+            .collect();
+        self.extractor.request(missing);
+        self.requested = times;
+    }
+
+    /// thumbnail for every requested time, the nearest one stands in until extracted
+    pub fn visible(&self) -> Vec<(f64, Arc<RenderImage>)> {
+        self.requested
+            .iter()
+            .filter_map(|t| {
+                let time_ms = (t * 1000.).round() as u64;
+                let before = self.thumbnails.range(..=time_ms).next_back();
+                let after = self.thumbnails.range(time_ms..).next();
+                let nearest = match (before, after) {
+                    (Some(b), Some(a)) if a.0 - time_ms < time_ms - b.0 => a,
+                    (Some(b), _) => b,
+                    (None, a) => a?,
+                };
+                Some((*t, nearest.1.clone()))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_times_reuse_coarser_levels() {
+        let coarse = grid_times(320., &(0.0..320.), 32);
+        assert_eq!(coarse.len(), 32);
+        assert_eq!(coarse[1], 10.);
+
+        // zoomed in, the grid stops at the smallest interval
+        let fine = grid_times(320., &(100.0..110.), 32);
+        assert!(fine.contains(&100.));
+        assert!(fine.iter().all(|t| (t / 0.625).fract() == 0.));
+    }
+
+    #[test]
+    fn cache_read_stops_at_corrupt_record() {
+        let mut bytes = vec![];
+        for (time_ms, width) in [(500u64, 2u32), (1000, u32::MAX)] {
+            bytes.extend_from_slice(&time_ms.to_le_bytes());
+            bytes.extend_from_slice(&width.to_le_bytes());
+            bytes.extend_from_slice(&THUMB_HEIGHT.to_le_bytes());
+        }
+        bytes.splice(16..16, vec![0u8; (2 * THUMB_HEIGHT * 4) as usize]);
+
+        let mut reader = bytes.as_slice();
+        let first = RawThumbnail::read(&mut reader).unwrap();
+        assert_eq!((first.time_ms, first.width), (500, 2));
+        assert!(RawThumbnail::read(&mut reader).is_none());
+    }
+}
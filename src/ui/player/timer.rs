@@ -1,8 +1,20 @@
-use std::time::Instant;
+use std::{
+    cell::Cell,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+/// audio clock goes on by wall time after nothing is consumed this long, audio ended before video
+static AUDIO_STALL: Duration = Duration::from_millis(500);
 
 pub struct Timer {
     start_point: Option<Instant>,
     played_us: Option<u64>,
+    // media time passed per wall time
+    rate: f64,
 }
 
 impl Timer {
@@ -10,6 +22,17 @@ impl Timer {
         Self {
             start_point: None,
             played_us: None,
+            rate: 1.0,
+        }
+    }
+
+    /// change speed, time played so far stays
+    pub fn set_rate(&mut self, rate: f64) {
+        let running = self.start_point.is_some();
+        self.stop();
+        self.rate = rate;
+        if running {
+            self.start();
         }
     }
 
@@ -21,7 +44,7 @@ impl Timer {
 
     pub fn stop(&mut self) {
         if let Some(p) = self.start_point.take() {
-            self.played_us = Some(p.elapsed().as_micros() as u64 + self.played_us.unwrap_or(0));
+            self.played_us = Some(self.scaled(p) + self.played_us.unwrap_or(0));
         }
     }
 
@@ -38,8 +61,7 @@ impl Timer {
     pub fn current_time_us(&self) -> u64 {
         let played = self.played_us.unwrap_or(0);
         if let Some(p) = self.start_point {
-            let elapsed_us = p.elapsed().as_micros().min(u64::MAX as u128) as u64;
-            played + elapsed_us
+            played + self.scaled(p)
         } else {
             played
         }
@@ -48,4 +70,104 @@ impl Timer {
     pub fn current_time_sec(&self) -> f64 {
         self.current_time_us() as f64 / 1_000_000.0
     }
+
+    /// media time since start point at current rate
+    fn scaled(&self, start_point: Instant) -> u64 {
+        let elapsed_us = start_point.elapsed().as_micros().min(u64::MAX as u128) as u64;
+        (elapsed_us as f64 * self.rate) as u64
+    }
+}
+
+/// playback time from samples the audio device consumed, so video follows audio
+pub struct AudioClock {
+    consumed: Arc<AtomicU64>,
+    // interleaved samples per second of output
+    samples_per_sec: f64,
+    // media time, consumed samples and rate when started, moved on when a stall ends
+    anchor: Cell<Option<(f64, u64, f64)>>,
+    // consumed samples when they last changed
+    progress: Cell<(u64, Instant)>,
+}
+
+impl AudioClock {
+    pub fn new(consumed: Arc<AtomicU64>, sample_rate: u32, channels: u16) -> Self {
+        Self {
+            consumed,
+            samples_per_sec: (sample_rate as f64 * channels.max(1) as f64).max(1.0),
+            anchor: Cell::new(None),
+            progress: Cell::new((0, Instant::now())),
+        }
+    }
+
+    /// count samples consumed from now on as played after time
+    pub fn start(&mut self, time: f64, rate: f64) {
+        let consumed = self.consumed.load(Ordering::Acquire);
+        self.anchor.set(Some((time, consumed, rate)));
+        self.progress.set((consumed, Instant::now()));
+    }
+
+    pub fn stop(&mut self) {
+        self.anchor.set(None);
+    }
+
+    /// media time, none when stopped, never goes back while running
+    pub fn time(&self) -> Option<f64> {
+        let (mut time, mut anchor, rate) = self.anchor.get()?;
+        let consumed = self.consumed.load(Ordering::Acquire);
+        let (last, since) = self.progress.get();
+        let stalled = since.elapsed().saturating_sub(AUDIO_STALL).as_secs_f64() * rate;
+        if consumed != last {
+            if stalled > 0.0 {
+                // wall time counted while stalled stays, samples count on from there
+                time += self.played(anchor, last, rate) + stalled;
+                anchor = last;
+                self.anchor.set(Some((time, anchor, rate)));
+            }
+            self.progress.set((consumed, Instant::now()));
+            return Some(time + self.played(anchor, consumed, rate));
+        }
+        Some(time + self.played(anchor, consumed, rate) + stalled)
+    }
+
+    /// media time of samples consumed between anchor and consumed
+    fn played(&self, anchor: u64, consumed: u64, rate: f64) -> f64 {
+        consumed.saturating_sub(anchor) as f64 / self.samples_per_sec * rate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn audio_clock_follows_consumed_samples() {
+        let consumed = Arc::new(AtomicU64::new(500));
+        let mut clock = AudioClock::new(consumed.clone(), 48_000, 2);
+        assert_eq!(clock.time(), None);
+
+        clock.start(10.0, 2.0);
+        consumed.fetch_add(48_000, Ordering::Release);
+        assert_eq!(clock.time(), Some(11.0));
+
+        clock.stop();
+        assert_eq!(clock.time(), None);
+    }
+
+    #[test]
+    fn audio_clock_keeps_stalled_time_when_samples_resume() {
+        let consumed = Arc::new(AtomicU64::new(0));
+        let clock = AudioClock::new(consumed.clone(), 1_000, 1);
+        clock.anchor.set(Some((0.0, 0, 1.0)));
+        // nothing consumed for a second, half of it counts by wall time
+        clock
+            .progress
+            .set((0, Instant::now() - AUDIO_STALL - Duration::from_millis(500)));
+        let stalled = clock.time().unwrap();
+        assert!(stalled >= 0.5);
+
+        consumed.fetch_add(100, Ordering::Release);
+        let resumed = clock.time().unwrap();
+        assert!(resumed >= stalled + 0.1);
+        assert!(clock.time().unwrap() >= resumed);
+    }
 }
@@ -1,9 +1,17 @@
-use std::sync::Arc;
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    path::PathBuf,
+    sync::Arc,
+};
 
 use gpui::{App, IntoElement, ParentElement, RenderImage, Styled, Window, div};
 use gpui_component::Root;
 use image::RgbaImage;
 
+use crate::config;
+
 pub fn generate_image_fallback(size: (u32, u32), frame: Vec<u8>) -> Arc<RenderImage> {
     let frame_len = frame.len();
 
@@ -37,3 +45,26 @@ pub fn render_notification_layer(
             .child(root.read(cx).notification.clone()),
     )
 }
+
+pub fn format_sec(sec: f64) -> String {
+    let millis = (sec.max(0.0) * 1_000.0).floor() as u64;
+    format!(
+        "{:02}:{:02}.{:02}",
+        millis / 60_000,
+        millis / 1_000 % 60,
+        millis % 100,
+    )
+}
+
+/// cache file for data of a source under cache dir, a modified source gets a new one
+pub fn source_cache_path(path: &PathBuf, kind: &str, suffix: &str) -> Option<PathBuf> {
+    let meta = fs::metadata(path).ok()?;
+    let mut hasher = DefaultHasher::new();
+    path.canonicalize()
+        .unwrap_or(path.clone())
+        .hash(&mut hasher);
+    meta.len().hash(&mut hasher);
+    meta.modified().ok().hash(&mut hasher);
+    let name = format!("{:016x}_{}", hasher.finish(), suffix);
+    Some(config::cache_dir().ok()?.join(kind).join(name))
+}
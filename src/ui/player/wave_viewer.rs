@@ -0,0 +1,124 @@
+use gpui::{
+    BorderStyle, Bounds, Corners, Element, IntoElement, LayoutId, Pixels, Style, point, px, quad,
+    relative, rgb, size,
+};
+
+use crate::ui::player::waveform::Bucket;
+
+/// seconds of audio shown around playhead
+pub static WAVE_VIEW_SEC: f64 = 10.;
+/// part of height the loudest bucket reaches
+static WAVE_VIEW_SCALE: f32 = 0.8;
+
+/// waveform in place of video frame for audio only files, playhead stays in middle
+pub struct WaveViewer {
+    columns: Vec<Bucket>,
+}
+
+impl WaveViewer {
+    pub fn new(columns: Vec<Bucket>) -> Self {
+        Self { columns }
+    }
+}
+
+impl Element for WaveViewer {
+    type RequestLayoutState = LayoutId;
+
+    type PrepaintState = ();
+
+    fn id(&self) -> Option<gpui::ElementId> {
+        None
+    }
+
+    fn source_location(&self) -> Option<&'static std::panic::Location<'static>> {
+        None
+    }
+
+    fn request_layout(
+        &mut self,
+        _: Option<&gpui::GlobalElementId>,
+        _: Option<&gpui::InspectorElementId>,
+        window: &mut gpui::Window,
+        cx: &mut gpui::App,
+    ) -> (gpui::LayoutId, Self::RequestLayoutState) {
+        let mut style = Style::default();
+
+        style.size.width = relative(1.0).into();
+        style.size.height = relative(1.0).into();
+
+        let layout_id = window.request_layout(style, None, cx);
+        (layout_id, layout_id)
+    }
+
+    fn prepaint(
+        &mut self,
+        _: Option<&gpui::GlobalElementId>,
+        _: Option<&gpui::InspectorElementId>,
+        _: gpui::Bounds<gpui::Pixels>,
+        _: &mut Self::RequestLayoutState,
+        _: &mut gpui::Window,
+        _: &mut gpui::App,
+    ) -> Self::PrepaintState {
+    }
+
+    fn paint(
+        &mut self,
+        _: Option<&gpui::GlobalElementId>,
+        _: Option<&gpui::InspectorElementId>,
+        bounds: gpui::Bounds<gpui::Pixels>,
+        _: &mut Self::RequestLayoutState,
+        _: &mut Self::PrepaintState,
+        window: &mut gpui::Window,
+        _: &mut gpui::App,
+    ) {
+        let middle = bounds.origin.y + bounds.size.height / 2.;
+        let full = bounds.size.height * WAVE_VIEW_SCALE;
+        if !self.columns.is_empty() {
+            let peak_color = rgb(0x3FA7D6);
+            let rms_color = rgb(0x9AD8F2);
+            let column = bounds.size.width / self.columns.len() as f32;
+            for (i, bucket) in self.columns.iter().enumerate() {
+                let x = bounds.origin.x + column * i as f32;
+                for (level, color) in [(bucket.peak, peak_color), (bucket.rms, rms_color)] {
+                    let height = (full * level.min(1.)).max(px(1.));
+                    window.paint_quad(quad(
+                        Bounds {
+                            origin: point(x, middle - height / 2.),
+                            size: size(column.max(px(1.)), height),
+                        },
+                        Corners::default(),
+                        color,
+                        px(0.),
+                        color,
+                        BorderStyle::default(),
+                    ));
+                }
+            }
+        }
+
+        // playhead
+        let playhead = Bounds::<Pixels> {
+            origin: point(
+                bounds.origin.x + bounds.size.width / 2. - px(1.),
+                bounds.origin.y,
+            ),
+            size: size(px(2.), bounds.size.height),
+        };
+        window.paint_quad(quad(
+            playhead,
+            Corners::default(),
+            gpui::white(),
+            px(0.),
+            gpui::white(),
+            BorderStyle::default(),
+        ));
+    }
+}
+
+impl IntoElement for WaveViewer {
+    type Element = Self;
+
+    fn into_element(self) -> Self::Element {
+        self
+    }
+}
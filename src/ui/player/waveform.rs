@@ -0,0 +1,279 @@
+use std::{
+    fs::{self, File},
+    io::{Read, Write},
+    ops::Range,
+    path::PathBuf,
+    thread,
+};
+
+use anyhow::anyhow;
+use async_channel::{Receiver, Sender};
+use ffmpeg_next::{
+    ChannelLayout, codec,
+    format::{self, sample::Type},
+    frame::Audio,
+    software::resampling,
+};
+
+use crate::ui::player::utils::source_cache_path;
+
+/// columns the visible part of waveform is merged into
+pub static WAVEFORM_COLUMNS: usize = 400;
+/// length of audio summed into one bucket
+static BUCKET_SEC: f64 = 0.02;
+/// buckets sent to ui at once while decoding
+static CHUNK_BUCKETS: usize = 500;
+
+/// loudness of a short piece of audio, in full scale
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Bucket {
+    pub peak: f32,
+    pub rms: f32,
+}
+
+impl Bucket {
+    /// one bucket standing for many
+    fn merge(buckets: &[Bucket]) -> Self {
+        if buckets.is_empty() {
+            return Self::default();
+        }
+        let peak = buckets.iter().map(|b| b.peak).fold(0., f32::max);
+        let square = buckets.iter().map(|b| b.rms * b.rms).sum::<f32>() / buckets.len() as f32;
+        Self {
+            peak,
+            rms: square.sqrt(),
+        }
+    }
+}
+
+/// sums mono samples into buckets
+struct BucketBuilder {
+    size: usize,
+    count: usize,
+    peak: f32,
+    square: f64,
+}
+
+impl BucketBuilder {
+    fn new(rate: u32) -> Self {
+        Self {
+            size: ((rate as f64 * BUCKET_SEC).round() as usize).max(1),
+            count: 0,
+            peak: 0.,
+            square: 0.,
+        }
+    }
+
+    fn push(&mut self, sample: f32) -> Option<Bucket> {
+        self.peak = self.peak.max(sample.abs());
+        self.square += (sample * sample) as f64;
+        self.count += 1;
+        if self.count < self.size {
+            return None;
+        }
+        self.finish()
+    }
+
+    /// bucket of samples pushed so far
+    fn finish(&mut self) -> Option<Bucket> {
+        if self.count == 0 {
+            return None;
+        }
+        let bucket = Bucket {
+            peak: self.peak.min(1.),
+            rms: (self.square / self.count as f64).sqrt() as f32,
+        };
+        self.count = 0;
+        self.peak = 0.;
+        self.square = 0.;
+        Some(bucket)
+    }
+}
+
+/// waveform of one audio rail, filled in while decoding in background
+pub struct Waveform {
+    pub ix: usize,
+    buckets: Vec<Bucket>,
+    // decoding stops once every receiver is dropped
+    chunks: Receiver<Vec<Bucket>>,
+}
+
+impl Waveform {
+    pub fn open(path: &PathBuf, ix: usize) -> Self {
+        let (chunk_tx, chunks) = async_channel::unbounded();
+        let path = path.clone();
+        thread::spawn(move || {
+            if let Err(e) = run_analyzer(&path, ix, chunk_tx) {
+                eprintln!("failed to analyze audio of {:?}: {}", path, e);
+            }
+        });
+        Self {
+            ix,
+            buckets: vec![],
+            chunks,
+        }
+    }
+
+    /// buckets as they are decoded
+    pub fn chunks(&self) -> Receiver<Vec<Bucket>> {
+        self.chunks.clone()
+    }
+
+    /// whether chunks come from this waveform
+    pub fn is_fed_by(&self, chunks: &Receiver<Vec<Bucket>>) -> bool {
+        self.chunks.same_channel(chunks)
+    }
+
+    pub fn extend(&mut self, buckets: Vec<Bucket>) {
+        self.buckets.extend(buckets);
+    }
+
+    /// buckets merged into columns across visible span, in seconds, silent before start
+    pub fn columns(&self, visible: Range<f64>, count: usize) -> Vec<Bucket> {
+        if count == 0 || visible.end <= visible.start {
+            return vec![];
+        }
+        let first = visible.start / BUCKET_SEC;
+        let per_column = (visible.end - visible.start) / BUCKET_SEC / count as f64;
+        (0..count)
+            .map(|i| {
+                let from = first + i as f64 * per_column;
+                if from < 0. {
+                    return Bucket::default();
+                }
+                let start = from.floor() as usize;
+                let end = ((first + (i + 1) as f64 * per_column).ceil() as usize).max(start + 1);
+                let end = end.min(self.buckets.len());
+                Bucket::merge(&self.buckets[start.min(end)..end])
+            })
+            .collect()
+    }
+}
+
+fn run_analyzer(path: &PathBuf, ix: usize, chunks: Sender<Vec<Bucket>>) -> anyhow::Result<()> {
+    let cache_path = source_cache_path(path, "waveforms", &format!("{}.bin", ix));
+    if let Some(buckets) = cache_path.as_ref().and_then(read_cache) {
+        let _ = chunks.send_blocking(buckets);
+        return Ok(());
+    }
+
+    let mut input = format::input(path)?;
+    let stream = input
+        .stream(ix)
+        .ok_or(anyhow!("failed to get target stream"))?;
+    let mut decoder = codec::context::Context::from_parameters(stream.parameters())?
+        .decoder()
+        .audio()?;
+    let mut resampler = resampling::Context::get(
+        decoder.format(),
+        decoder.channel_layout(),
+        decoder.rate(),
+        format::Sample::F32(Type::Packed),
+        ChannelLayout::MONO,
+        decoder.rate(),
+    )?;
+    let mut builder = BucketBuilder::new(decoder.rate());
+
+    let mut decoded = Audio::empty();
+    let mut resampled = Audio::empty();
+    let mut all = vec![];
+    let mut chunk = vec![];
+    for (stream, packet) in input.packets() {
+        if stream.index() != ix || decoder.send_packet(&packet).is_err() {
+            continue;
+        }
+        while decoder.receive_frame(&mut decoded).is_ok() {
+            resampler.run(&decoded, &mut resampled)?;
+            let samples: &[f32] = unsafe {
+                std::slice::from_raw_parts(
+                    resampled.data(0).as_ptr() as *const f32,
+                    resampled.samples(),
+                )
+            };
+            chunk.extend(samples.iter().filter_map(|s| builder.push(*s)));
+        }
+        if chunk.len() >= CHUNK_BUCKETS {
+            all.extend_from_slice(&chunk);
+            // file closed or another rail picked
+            if chunks.send_blocking(std::mem::take(&mut chunk)).is_err() {
+                return Ok(());
+            }
+        }
+    }
+    chunk.extend(builder.finish());
+    all.extend_from_slice(&chunk);
+    let _ = chunks.send_blocking(chunk);
+
+    if let Some(p) = cache_path
+        && let Err(e) = write_cache(&p, &all)
+    {
+        eprintln!("failed to write waveform cache: {}", e);
+    }
+    Ok(())
+}
+
+/// buckets of a whole rail, none when missing or broken
+fn read_cache(path: &PathBuf) -> Option<Vec<Bucket>> {
+    let mut bytes = vec![];
+    File::open(path).ok()?.read_to_end(&mut bytes).ok()?;
+    if bytes.len() % 8 != 0 {
+        return None;
+    }
+    let level = |b: &[u8]| f32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+    Some(
+        bytes
+            .chunks_exact(8)
+            .map(|b| Bucket {
+                peak: level(&b[..4]),
+                rms: level(&b[4..]),
+            })
+            .collect(),
+    )
+}
+
+fn write_cache(path: &PathBuf, buckets: &[Bucket]) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut bytes = Vec::with_capacity(buckets.len() * 8);
+    for bucket in buckets {
+        bytes.extend_from_slice(&bucket.peak.to_le_bytes());
+        bytes.extend_from_slice(&bucket.rms.to_le_bytes());
+    }
+    File::create(path)?.write_all(&bytes)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_keep_peak_and_merge_rms() {
+        // 100 samples per bucket
+        let mut builder = BucketBuilder::new(5000);
+        let mut buckets: Vec<Bucket> = (0..250)
+            .map(|i| if i % 2 == 0 { 0.5 } else { -0.5 })
+            .filter_map(|s| builder.push(s))
+            .collect();
+        buckets.extend(builder.finish());
+        assert_eq!(buckets.len(), 3);
+        assert_eq!(
+            buckets[0],
+            Bucket {
+                peak: 0.5,
+                rms: 0.5
+            }
+        );
+
+        let merged = Bucket::merge(&[
+            Bucket { peak: 0.8, rms: 0. },
+            Bucket {
+                peak: 0.2,
+                rms: 0.5,
+            },
+        ]);
+        assert_eq!(merged.peak, 0.8);
+        assert!((merged.rms - 0.125f32.sqrt()).abs() < 1e-6);
+    }
+}
@@ -1,17 +1,135 @@
 use std::{ops::Range, sync::Arc};
 
 use gpui::{
-    AbsoluteLength, App, BorderStyle, Bounds, Corners, DefiniteLength, Element, ElementId, Hsla,
-    IntoElement, LayoutId, Length, MouseButton, MouseDownEvent, Path, Pixels, Point, Size, Style,
-    Window, point, px, quad, relative, rgb, size,
+    AbsoluteLength, App, BorderStyle, Bounds, ContentMask, Corners, DefiniteLength, Element,
+    ElementId, Hsla, IntoElement, LayoutId, Length, MouseButton, MouseDownEvent, MouseMoveEvent,
+    MouseUpEvent, Path, Pixels, Point, RenderImage, ScrollWheelEvent, SharedString, Size, Style,
+    TextRun, Window, point, px, quad, relative, rgb, size,
 };
 
+use crate::ui::player::{thumbnail::THUMB_HEIGHT, utils::format_sec, waveform::Bucket};
+
+/// shortest span the timeline zooms into
+static MIN_VISIBLE_SEC: f64 = 2.;
+/// where playhead lands after the view pages to follow it
+static FOLLOW_MARGIN: f32 = 0.1;
+/// ruler ticks are at least this far apart
+static MIN_TICK_SPACING: Pixels = px(90.);
+static TICK_STEPS: [f64; 17] = [
+    0.1, 0.2, 0.5, 1., 2., 5., 10., 15., 30., 60., 120., 300., 600., 900., 1800., 3600., 7200.,
+];
+
+/// height of waveform lane under the filmstrip
+static WAVE_HEIGHT: Pixels = px(32.);
+
+/// range handles are grabbed this close to them
+static HANDLE_GRAB: Pixels = px(6.);
+
+/// part of timeline held by the mouse
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DragTarget {
+    Playhead,
+    RangeStart,
+    RangeEnd,
+}
+
+/// wheel input on timeline, positions are fractions of its width
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimelineScroll {
+    Zoom { factor: f32, at: f32 },
+    Pan(f32),
+}
+
+/// visible part of timeline as percentage of progress, zoom 1 shows everything
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimelineViewport {
+    pub start: f32,
+    pub zoom: f32,
+}
+
+impl Default for TimelineViewport {
+    fn default() -> Self {
+        Self {
+            start: 0.,
+            zoom: 1.,
+        }
+    }
+}
+
+impl TimelineViewport {
+    pub fn width(&self) -> f32 {
+        1. / self.zoom
+    }
+
+    pub fn end(&self) -> f32 {
+        self.start + self.width()
+    }
+
+    /// percent of progress at a fraction of timeline width
+    pub fn percent_at(&self, fraction: f32) -> f32 {
+        self.start + fraction * self.width()
+    }
+
+    /// fraction of timeline width a percent of progress is drawn at
+    pub fn fraction_of(&self, percent: f32) -> f32 {
+        (percent - self.start) * self.zoom
+    }
+
+    /// apply wheel input, zoom stops when a few seconds fill the timeline
+    pub fn scroll(&mut self, scroll: TimelineScroll, duration: f64) {
+        match scroll {
+            TimelineScroll::Zoom { factor, at } => {
+                let anchor = self.percent_at(at);
+                self.zoom_at(factor, anchor, (duration / MIN_VISIBLE_SEC) as f32);
+            }
+            TimelineScroll::Pan(delta) => self.pan(delta * self.width()),
+        }
+    }
+
+    /// zoom keeping the anchor percent at the same place
+    pub fn zoom_at(&mut self, factor: f32, anchor: f32, max_zoom: f32) {
+        let fraction = self.fraction_of(anchor);
+        self.zoom = (self.zoom * factor).clamp(1., max_zoom.max(1.));
+        self.start = anchor - fraction * self.width();
+        self.clamp();
+    }
+
+    pub fn pan(&mut self, delta: f32) {
+        self.start += delta;
+        self.clamp();
+    }
+
+    /// page to the percent when it leaves the view
+    pub fn follow(&mut self, percent: f32) {
+        if percent < self.start || percent > self.end() {
+            self.start = percent - self.width() * FOLLOW_MARGIN;
+            self.clamp();
+        }
+    }
+
+    fn clamp(&mut self) {
+        self.start = self.start.clamp(0., 1. - self.width());
+    }
+}
+
 pub struct Timeline {
     id: ElementId,
     percent: f32,
     origin_point: Point<Pixels>,
-    on_click: Option<Arc<Box<dyn Fn(f32, &mut App) + 'static>>>,
+    on_drag: Option<Arc<Box<dyn Fn(DragTarget, f32, &mut App) + 'static>>>,
+    on_drop: Option<Arc<Box<dyn Fn(&mut App) + 'static>>>,
+    on_scroll: Option<Arc<Box<dyn Fn(TimelineScroll, &mut App) + 'static>>>,
     range: Range<Option<f32>>,
+    ranges: Vec<Range<f32>>,
+    markers: Vec<f32>,
+    // filmstrip images at percent of progress
+    thumbnails: Vec<(f32, Arc<RenderImage>)>,
+    // loudness columns across the visible part
+    waveform: Vec<Bucket>,
+    dragging: Option<DragTarget>,
+    viewport: TimelineViewport,
+    // seconds, labels the ruler
+    duration: f64,
 }
 
 impl Timeline {
@@ -20,18 +138,227 @@ impl Timeline {
             id: id.into(),
             percent: percent,
             origin_point: point(px(0.), px(0.)),
-            on_click: None,
+            on_drag: None,
+            on_drop: None,
+            on_scroll: None,
             range,
+            ranges: vec![],
+            markers: vec![],
+            thumbnails: vec![],
+            waveform: vec![],
+            dragging: None,
+            viewport: TimelineViewport::default(),
+            duration: 0.,
         }
     }
 
-    pub fn on_click(mut self, handler: impl Fn(f32, &mut App) + 'static) -> Self {
-        self.on_click = Some(Arc::new(Box::new(handler)));
+    /// ranges already added to list, painted under selection
+    pub fn ranges(mut self, ranges: Vec<Range<f32>>) -> Self {
+        self.ranges = ranges;
+        self
+    }
+
+    /// marker positions as percentage of progress
+    pub fn markers(mut self, markers: Vec<f32>) -> Self {
+        self.markers = markers;
+        self
+    }
+
+    pub fn viewport(mut self, viewport: TimelineViewport, duration: f64) -> Self {
+        self.viewport = viewport;
+        self.duration = duration;
+        self
+    }
+
+    pub fn thumbnails(mut self, thumbnails: Vec<(f32, Arc<RenderImage>)>) -> Self {
+        self.thumbnails = thumbnails;
+        self
+    }
+
+    /// audio buckets drawn across the visible range
+    pub fn waveform(mut self, waveform: Vec<Bucket>) -> Self {
+        self.waveform = waveform;
+        self
+    }
+
+    /// target held by the owner since the last press
+    pub fn dragging(mut self, dragging: Option<DragTarget>) -> Self {
+        self.dragging = dragging;
+        self
+    }
+
+    /// called on press and on every move while dragging, with the percent under mouse
+    pub fn on_drag(mut self, handler: impl Fn(DragTarget, f32, &mut App) + 'static) -> Self {
+        self.on_drag = Some(Arc::new(Box::new(handler)));
+        self
+    }
+
+    pub fn on_drop(mut self, handler: impl Fn(&mut App) + 'static) -> Self {
+        self.on_drop = Some(Arc::new(Box::new(handler)));
         self
     }
 
+    /// timestamp box next to the hover line
+    fn paint_hover_time(
+        &self,
+        bounds: Bounds<Pixels>,
+        x: Pixels,
+        window: &mut Window,
+        cx: &mut App,
+    ) {
+        let fraction = (x - bounds.origin.x) / bounds.size.width;
+        let sec = self.duration * self.viewport.percent_at(fraction) as f64;
+        let text: SharedString = format_sec(sec).into();
+        let run = TextRun {
+            len: text.len(),
+            font: window.text_style().font(),
+            color: gpui::white().into(),
+            background_color: None,
+            underline: None,
+            strikethrough: None,
+        };
+        let line = window.text_system().shape_line(text, px(11.), &[run], None);
+        let box_size = size(line.width + px(8.), px(16.));
+        // flip to the left side near the right end
+        let box_x = if x + px(4.) + box_size.width > bounds.origin.x + bounds.size.width {
+            x - px(4.) - box_size.width
+        } else {
+            x + px(4.)
+        };
+        let box_origin = point(box_x, self.origin_point.y + px(18.));
+        window.paint_quad(quad(
+            Bounds {
+                origin: box_origin,
+                size: box_size,
+            },
+            Corners::all(px(3.)),
+            gpui::black().alpha(0.8),
+            px(0.),
+            gpui::white(),
+            BorderStyle::default(),
+        ));
+        let _ = line.paint(box_origin + point(px(4.), px(2.)), px(12.), window, cx);
+    }
+
+    /// ctrl + wheel zooms, wheel pans
+    pub fn on_scroll(mut self, handler: impl Fn(TimelineScroll, &mut App) + 'static) -> Self {
+        self.on_scroll = Some(Arc::new(Box::new(handler)));
+        self
+    }
+
+    /// distance of a percent of progress from the left side
+    fn x_offset(&self, width: Pixels, percent: f32) -> Pixels {
+        width * self.viewport.fraction_of(percent)
+    }
+
     fn indicator_width(&self, b: gpui::Bounds<gpui::Pixels>) -> Pixels {
-        (b.size.width * self.percent).round()
+        self.x_offset(b.size.width, self.percent).round()
+    }
+
+    /// row of thumbnails under the ruler
+    fn paint_filmstrip(&self, bounds: Bounds<Pixels>, window: &mut Window) {
+        let height = px(THUMB_HEIGHT as f32);
+        let y = self.origin_point.y + px(44.);
+        window.paint_quad(quad(
+            Bounds {
+                origin: point(bounds.origin.x, y),
+                size: size(bounds.size.width, height),
+            },
+            Corners::default(),
+            gpui::black().alpha(0.4),
+            px(0.),
+            gpui::white(),
+            BorderStyle::default(),
+        ));
+        for (percent, image) in self.thumbnails.iter() {
+            let image_size = image.size(0);
+            let width = height * (image_size.width.0 as f32 / image_size.height.0.max(1) as f32);
+            let x = bounds.origin.x + self.x_offset(bounds.size.width, *percent).round();
+            let b = Bounds::new(point(x, y), size(width, height));
+            let _ = window.paint_image(b, b, Corners::all(px(0.)), image.clone(), 0, false);
+        }
+    }
+
+    /// loudness of audio rail under the filmstrip, peak behind rms
+    fn paint_waveform(&self, bounds: Bounds<Pixels>, window: &mut Window) {
+        let y = self.origin_point.y + px(48. + THUMB_HEIGHT as f32);
+        window.paint_quad(quad(
+            Bounds {
+                origin: point(bounds.origin.x, y),
+                size: size(bounds.size.width, WAVE_HEIGHT),
+            },
+            Corners::default(),
+            gpui::black().alpha(0.4),
+            px(0.),
+            gpui::white(),
+            BorderStyle::default(),
+        ));
+        if self.waveform.is_empty() {
+            return;
+        }
+        let peak_color = rgb(0x3FA7D6);
+        let rms_color = rgb(0x9AD8F2);
+        let column = bounds.size.width / self.waveform.len() as f32;
+        let middle = y + WAVE_HEIGHT / 2.;
+        for (i, bucket) in self.waveform.iter().enumerate() {
+            let x = bounds.origin.x + column * i as f32;
+            for (level, color) in [(bucket.peak, peak_color), (bucket.rms, rms_color)] {
+                let height = (WAVE_HEIGHT * level.min(1.)).max(px(1.));
+                window.paint_quad(quad(
+                    Bounds {
+                        origin: point(x, middle - height / 2.),
+                        size: size(column.max(px(1.)), height),
+                    },
+                    Corners::default(),
+                    color,
+                    px(0.),
+                    color,
+                    BorderStyle::default(),
+                ));
+            }
+        }
+    }
+
+    fn paint_ruler(&self, bounds: Bounds<Pixels>, window: &mut Window, cx: &mut App) {
+        let visible = self.duration * self.viewport.width() as f64;
+        if visible <= 0. {
+            return;
+        }
+        let color = gpui::white().alpha(0.6);
+        let step = tick_step(visible, bounds.size.width);
+        let y = self.origin_point.y + px(20.);
+        let first = (self.duration * self.viewport.start as f64 / step).ceil() as i64;
+        let last = (self.duration * self.viewport.end() as f64 / step).floor() as i64;
+        for i in first..=last {
+            let sec = i as f64 * step;
+            let x = bounds.origin.x
+                + self
+                    .x_offset(bounds.size.width, (sec / self.duration) as f32)
+                    .round();
+            window.paint_quad(quad(
+                Bounds {
+                    origin: point(x, y),
+                    size: size(px(1.), px(5.)),
+                },
+                Corners::default(),
+                color,
+                px(0.),
+                color,
+                BorderStyle::default(),
+            ));
+
+            let text: SharedString = ruler_label(sec, step).into();
+            let run = TextRun {
+                len: text.len(),
+                font: window.text_style().font(),
+                color,
+                background_color: None,
+                underline: None,
+                strikethrough: None,
+            };
+            let line = window.text_system().shape_line(text, px(10.), &[run], None);
+            let _ = line.paint(point(x + px(3.), y + px(2.)), px(12.), window, cx);
+        }
     }
 }
 
@@ -58,8 +385,9 @@ impl Element for Timeline {
         let mut style = Style::default();
 
         style.size.width = relative(1.0).into();
-        style.size.height =
-            Length::Definite(DefiniteLength::Absolute(AbsoluteLength::Pixels(px(50.))));
+        style.size.height = Length::Definite(DefiniteLength::Absolute(AbsoluteLength::Pixels(px(
+            106. + THUMB_HEIGHT as f32,
+        ))));
 
         let layout_id = window.request_layout(style, None, cx);
         (layout_id, layout_id)
@@ -85,8 +413,78 @@ impl Element for Timeline {
         _: &mut Self::RequestLayoutState,
         _: &mut Self::PrepaintState,
         window: &mut gpui::Window,
-        _: &mut gpui::App,
+        cx: &mut gpui::App,
     ) {
+        window.with_content_mask(Some(ContentMask { bounds }), |window| {
+            self.paint_content(bounds, window, cx);
+        });
+
+        let origin_x = bounds.origin.x;
+        let viewport = self.viewport;
+        // dragging goes on outside of timeline, clamped to what is visible
+        let percent_at = move |x: Pixels| {
+            viewport.percent_at(((x - origin_x) / bounds.size.width).clamp(0., 1.))
+        };
+        if let Some(on_drag) = self.on_drag.clone() {
+            let handle_x = |percent: Option<f32>| {
+                percent.map(|p| origin_x + self.x_offset(bounds.size.width, p))
+            };
+            let (start_x, end_x) = (handle_x(self.range.start), handle_x(self.range.end));
+            let press = on_drag.clone();
+            window.on_mouse_event(move |e: &MouseDownEvent, phase, _, cx| {
+                if phase.bubble() && e.button == MouseButton::Left && bounds.contains(&e.position) {
+                    let target = hit_target(start_x, end_x, e.position.x);
+                    press(target, percent_at(e.position.x), cx);
+                    cx.stop_propagation();
+                }
+            });
+
+            if let Some(target) = self.dragging {
+                window.on_mouse_event(move |e: &MouseMoveEvent, phase, _, cx| {
+                    if phase.bubble() && e.pressed_button == Some(MouseButton::Left) {
+                        on_drag(target, percent_at(e.position.x), cx);
+                    }
+                });
+            }
+        }
+        if let (Some(on_drop), Some(_)) = (self.on_drop.clone(), self.dragging) {
+            window.on_mouse_event(move |e: &MouseUpEvent, phase, _, cx| {
+                if phase.bubble() && e.button == MouseButton::Left {
+                    on_drop(cx);
+                }
+            });
+        }
+
+        if let Some(on_scroll) = self.on_scroll.clone() {
+            window.on_mouse_event(move |e: &ScrollWheelEvent, phase, _, cx| {
+                if !phase.bubble() || !bounds.contains(&e.position) {
+                    return;
+                }
+                let delta = e.delta.pixel_delta(px(20.));
+                let scroll = if e.modifiers.control || e.modifiers.platform {
+                    // touchpad pinch arrives as ctrl + wheel
+                    TimelineScroll::Zoom {
+                        factor: (delta.y / px(200.)).exp(),
+                        at: (e.position.x - origin_x) / bounds.size.width,
+                    }
+                } else {
+                    // timeline has no vertical scroll, so the wheel pans too
+                    let dx = if delta.x.abs() > delta.y.abs() {
+                        delta.x
+                    } else {
+                        delta.y
+                    };
+                    TimelineScroll::Pan(-(dx / bounds.size.width))
+                };
+                on_scroll(scroll, cx);
+                cx.stop_propagation();
+            });
+        }
+    }
+}
+
+impl Timeline {
+    fn paint_content(&self, bounds: Bounds<Pixels>, window: &mut Window, cx: &mut App) {
         let scale = window.scale_factor();
 
         let base_played = rgb(0x0091FF);
@@ -97,6 +495,8 @@ impl Element for Timeline {
         let selected_unplay = rgb(0x978F5C);
 
         let point_color = rgb(0xFFF29A);
+        let listed_color = rgb(0x2F8F5A);
+        let marker_color = rgb(0xFF8A3D);
 
         let origin_x = bounds.origin.x;
         let indi_width = self.indicator_width(bounds);
@@ -122,7 +522,7 @@ impl Element for Timeline {
             Bounds {
                 origin: self.origin_point,
                 size: Size {
-                    width: indi_width - px(1.),
+                    width: indi_width.max(px(1.)).min(bounds.size.width) - px(1.),
                     height: base_h,
                 },
             },
@@ -133,12 +533,34 @@ impl Element for Timeline {
             BorderStyle::default(),
         ));
 
+        // listed ranges
+        for range in self.ranges.iter() {
+            let point_a = origin_x + self.x_offset(bounds.size.width, range.start);
+            let point_b = origin_x + self.x_offset(bounds.size.width, range.end);
+            if point_a < point_b {
+                window.paint_quad(quad(
+                    Bounds {
+                        origin: point(point_a.round(), self.origin_point.y),
+                        size: Size {
+                            width: point_b - point_a,
+                            height: base_h,
+                        },
+                    },
+                    Corners::default(),
+                    listed_color,
+                    px(1.),
+                    gpui::black(),
+                    BorderStyle::default(),
+                ));
+            }
+        }
+
         // selected range
         if self.range.start.is_some() || self.range.end.is_some() {
             let start = self.range.start.unwrap_or(0.);
             let end = self.range.end.unwrap_or(1.);
-            let point_a = origin_x + bounds.size.width * start;
-            let point_b = origin_x + (bounds.size.width + px(1.)) * end;
+            let point_a = origin_x + self.x_offset(bounds.size.width, start);
+            let point_b = origin_x + self.x_offset(bounds.size.width, end) + px(1.);
 
             let divide_point = if indi_x > point_b {
                 // indicator in range
@@ -187,15 +609,20 @@ impl Element for Timeline {
 
         // draw range start point
         if let Some(start) = self.range.start {
-            let point = origin_x + (bounds.size.width * start).round() - px(1.);
+            let point = origin_x + self.x_offset(bounds.size.width, start).round() - px(1.);
             paint_dashline(window, point, self.origin_point.y - px(7.5), point_color);
         }
         // draw range end point
         if let Some(end) = self.range.end {
-            let point = origin_x + (bounds.size.width * end).round();
+            let point = origin_x + self.x_offset(bounds.size.width, end).round();
             paint_dashline(window, point, self.origin_point.y - px(7.5), point_color);
         }
 
+        for marker in self.markers.iter() {
+            let x = origin_x + self.x_offset(bounds.size.width, *marker).round();
+            paint_marker(window, &self.origin_point, base_h, x, marker_color);
+        }
+
         print_indicator(
             window,
             scale,
@@ -205,6 +632,10 @@ impl Element for Timeline {
             gpui::white(),
         );
 
+        self.paint_ruler(bounds, window, cx);
+        self.paint_filmstrip(bounds, window);
+        self.paint_waveform(bounds, window);
+
         let mouse_pos = window.mouse_position();
         if bounds.contains(&mouse_pos) {
             print_indicator(
@@ -215,16 +646,9 @@ impl Element for Timeline {
                 mouse_pos.x,
                 gpui::white().alpha(0.5),
             );
-        }
-
-        if let Some(on_click) = self.on_click.clone() {
-            window.on_mouse_event(move |e: &MouseDownEvent, phase, _, cx| {
-                if phase.bubble() && e.button == MouseButton::Left && bounds.contains(&e.position) {
-                    let percent = (e.position.x - origin_x) / bounds.size.width;
-                    on_click(percent, cx);
-                    cx.stop_propagation();
-                }
-            });
+            if self.duration > 0. {
+                self.paint_hover_time(bounds, mouse_pos.x, window, cx);
+            }
         }
     }
 }
@@ -237,6 +661,17 @@ impl IntoElement for Timeline {
     }
 }
 
+/// range handle near x, playhead elsewhere
+fn hit_target(start_x: Option<Pixels>, end_x: Option<Pixels>, x: Pixels) -> DragTarget {
+    let grab = |handle: Option<Pixels>| handle.map(|h| (h - x).abs()).filter(|d| *d <= HANDLE_GRAB);
+    match (grab(start_x), grab(end_x)) {
+        (Some(start), Some(end)) if end < start => DragTarget::RangeEnd,
+        (Some(_), _) => DragTarget::RangeStart,
+        (None, Some(_)) => DragTarget::RangeEnd,
+        (None, None) => DragTarget::Playhead,
+    }
+}
+
 fn print_indicator(
     window: &mut Window,
     scale: f32,
@@ -277,6 +712,38 @@ fn print_indicator(
     ));
 }
 
+/// flag under the bar with a line across it
+fn paint_marker(
+    window: &mut Window,
+    origin_point: &Point<Pixels>,
+    base_h: Pixels,
+    x: Pixels,
+    color: impl Into<Hsla>,
+) {
+    let color = color.into();
+    let head_size = px(4.);
+    let head_y = origin_point.y + base_h + px(2.);
+
+    let mut path = Path::new(*origin_point);
+    path.move_to(point(x, head_y)); // top corner
+    path.line_to(point(x + head_size, head_y + head_size)); // right bottom
+    path.line_to(point(x - head_size, head_y + head_size)); // left bottom
+    path.line_to(point(x, head_y)); // back to start
+    window.paint_path(path, color);
+
+    window.paint_quad(quad(
+        Bounds {
+            origin: point(x - px(1.), origin_point.y),
+            size: size(px(2.), base_h),
+        },
+        Corners::default(),
+        color,
+        px(0.),
+        color,
+        BorderStyle::default(),
+    ));
+}
+
 fn paint_dashline(window: &mut Window, x: Pixels, y_start: Pixels, color: impl Into<Hsla>) {
     let dash_height = px(4.0); // 每段虚线的高度
     let gap_height = px(1.0); // 间隔高度
@@ -310,3 +777,65 @@ fn paint_dashline(window: &mut Window, x: Pixels, y_start: Pixels, color: impl I
         current_y += dash_height + gap_height;
     }
 }
+
+/// ruler step in seconds, smallest one that keeps labels apart
+fn tick_step(visible_sec: f64, width: Pixels) -> f64 {
+    let last = TICK_STEPS[TICK_STEPS.len() - 1];
+    TICK_STEPS
+        .iter()
+        .copied()
+        .find(|step| width * (step / visible_sec) as f32 >= MIN_TICK_SPACING)
+        .unwrap_or(last)
+}
+
+/// `h:mm:ss` past an hour, tenths only when ticks are closer than a second
+fn ruler_label(sec: f64, step: f64) -> String {
+    let tenths = (sec * 10.).round() as u64;
+    let (h, m, s) = (tenths / 36_000, tenths / 600 % 60, tenths / 10 % 60);
+    let clock = if h > 0 {
+        format!("{}:{:02}:{:02}", h, m, s)
+    } else {
+        format!("{}:{:02}", m, s)
+    };
+    if step < 1. {
+        format!("{}.{}", clock, tenths % 10)
+    } else {
+        clock
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zoom_keeps_anchor_in_place() {
+        let mut viewport = TimelineViewport::default();
+        viewport.zoom_at(4., 0.5, 100.);
+        assert_eq!(viewport.zoom, 4.);
+        assert!((viewport.fraction_of(0.5) - 0.5).abs() < 1e-6);
+
+        // can not scroll past the end
+        viewport.pan(1.);
+        assert!((viewport.end() - 1.).abs() < 1e-6);
+
+        viewport.follow(0.1);
+        assert!(viewport.start <= 0.1 && viewport.end() >= 0.1);
+    }
+
+    #[test]
+    fn press_near_handle_grabs_it() {
+        let (start, end) = (Some(px(100.)), Some(px(104.)));
+        assert_eq!(hit_target(start, end, px(97.)), DragTarget::RangeStart);
+        assert_eq!(hit_target(start, end, px(105.)), DragTarget::RangeEnd);
+        assert_eq!(hit_target(start, None, px(150.)), DragTarget::Playhead);
+    }
+
+    #[test]
+    fn ruler_steps_grow_with_visible_span() {
+        assert_eq!(tick_step(10., px(900.)), 1.);
+        assert_eq!(tick_step(3. * 3600., px(900.)), 1800.);
+        assert_eq!(ruler_label(3725., 5.), "1:02:05");
+        assert_eq!(ruler_label(2.5, 0.5), "0:02.5");
+    }
+}
@@ -2,29 +2,46 @@ use std::{ops::Range, path::PathBuf, time::Duration};
 
 use app_assets::icons::{self, rounded};
 use gpui::{
-    AnyElement, AppContext, Context, Entity, ExternalPaths, FocusHandle, Focusable,
-    InteractiveElement, IntoElement, ParentElement, Render, Styled, Task, Window, div,
+    AnyElement, App, AppContext, BorrowAppContext, Context, Entity, ExternalPaths, FocusHandle,
+    Focusable, InteractiveElement, IntoElement, ParentElement, Render, Styled, Task, Window, div,
     prelude::FluentBuilder, px, rgba, svg,
 };
 use gpui_component::{
     ActiveTheme, Colorize, Root, StyledExt, TitleBar, WindowExt, menu::AppMenuBar,
+    notification::Notification,
 };
+use rust_i18n::t;
 
 use crate::{
-    Back, Forward, SetEnd, SetStart, SwitchPlay, VolumeDown, VolumeUp,
-    components::app_menu::{self, ClearSelectedRange, Close},
-    config::AppConfig,
-    models::model::OutputParams,
+    Back, Forward, SetEnd, SetStart, ShuttleBack, ShuttleForward, ShuttleStop, SwitchPlay,
+    VolumeDown, VolumeUp,
+    cli::LaunchArgs,
+    components::app_menu::{
+        self, AddMarker, AddRange, AudioMixer, ClearSelectedRange, Close, Markers, NextMarker,
+        OpenProject, PrevMarker, RemoveRange, SaveProject, StepBack, StepForward,
+    },
+    config::{AppConfig, RecentFile},
+    models::{
+        model::{ClipRange, Marker, OutputParams},
+        project::{PROJECT_EXTENSION, Project, project_path_for},
+    },
     ui::{
         button::RoundButton,
         chip::Chip,
         player::{
-            player::{PlayState, Player},
+            player::{PLAYBACK_RATES, PlayState, Player},
             settings::PlayerSettings,
             size::PlayerSize,
-            utils,
+            thumbnail::Filmstrip,
+            utils::{self, format_sec},
+            wave_viewer::{WAVE_VIEW_SEC, WaveViewer},
+            waveform::{WAVEFORM_COLUMNS, Waveform},
+        },
+        timeline::{DragTarget, Timeline, TimelineViewport},
+        views::{
+            markers::{MarkersView, build_markers_dialog},
+            mixer::{MixerView, build_mixer_dialog},
         },
-        timeline::Timeline,
     },
 };
 
@@ -47,6 +64,14 @@ pub struct MyApp {
     message: Option<String>,
     message_icon: Option<String>,
     message_mgr: MessageState,
+    // seek again once the player reopened for the audio rail
+    pending_seek: Option<f64>,
+    // file playing now, output path changes before the old one closes
+    opened: Option<PathBuf>,
+    timeline_viewport: TimelineViewport,
+    timeline_drag: Option<DragTarget>,
+    filmstrip: Option<Filmstrip>,
+    waveform: Option<Waveform>,
 }
 
 impl MyApp {
@@ -60,6 +85,13 @@ impl MyApp {
         let focus_handle = cx.focus_handle();
         Self::listen_open(&param_entity, cx);
         Self::listen_settings(&settings, cx);
+        cx.on_release(|this, cx| this.remember_position(cx))
+            .detach();
+        cx.on_app_quit(|this, cx| {
+            this.remember_position(cx);
+            async {}
+        })
+        .detach();
 
         Self {
             app_menu,
@@ -75,16 +107,38 @@ impl MyApp {
             message: None,
             message_icon: None,
             message_mgr: MessageState::None,
+            pending_seek: None,
+            opened: None,
+            timeline_viewport: TimelineViewport::default(),
+            timeline_drag: None,
+            filmstrip: None,
+            waveform: None,
         }
     }
 
-    /// handle open and play file
-    pub fn open_file(&mut self, cx: &mut Context<Self>, path: &PathBuf) {
+    /// handle open and play file, failure is shown as message
+    pub fn open_file(&mut self, cx: &mut Context<Self>, path: &PathBuf) -> anyhow::Result<()> {
         if self.player.is_init() {
             self.close_file(cx);
         }
-        self.player.open(cx, &path).unwrap();
-        self.player.start_play(cx, None);
+        if let Err(e) = self.player.open(cx, &path) {
+            eprintln!("failed to open {:?}: {}", path, e);
+            // previous file is closed already
+            self.output_parames.update(cx, |p, _| p.path = None);
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            self.show_message(
+                cx,
+                t!("common.open_failed", name = name, error = e.to_string()).to_string(),
+                None,
+                Some(Duration::from_secs(3)),
+            );
+            return Err(e);
+        }
+        self.player.start_play(cx, None, &[]);
+        self.open_filmstrip(cx, path);
 
         // init settings params
         let params = self.output_parames.read(cx);
@@ -94,45 +148,313 @@ impl MyApp {
             self.settings.update(cx, |s, cx| {
                 s.audio_ix = audio_ix;
                 s.audio_rails = audio_rails;
+                s.mix.clear();
                 cx.notify();
             });
+        } else {
+            // nothing to pick from without audio, player stays as opened
+            self.settings.update(cx, |s, _| {
+                s.audio_rails.clear();
+                s.mix.clear();
+            });
+        }
+
+        // resume where the file was left
+        let recent = cx
+            .global::<AppConfig>()
+            .recent_file(path)
+            .cloned()
+            .unwrap_or(RecentFile {
+                path: path.clone(),
+                position: 0.,
+                selection: None,
+            });
+        if let Some(selection) = recent.selection.as_ref() {
+            self.select_time(cx, selection);
+        }
+        if recent.position > 0. {
+            self.seek_after_open(recent.position);
         }
+        cx.update_global(|g: &mut AppConfig, _| {
+            g.remember_file(recent);
+            g.save();
+        });
+        self.opened = Some(path.clone());
         cx.notify();
+        Ok(())
+    }
+
+    /// open file given on command line, then seek and preset the A/B range
+    pub fn open_launch(&mut self, cx: &mut Context<Self>, launch: &LaunchArgs) {
+        let Some(path) = launch.path.as_ref() else {
+            return;
+        };
+        if !path.is_file() {
+            eprintln!("failed to open {:?}: not a file", path);
+            return;
+        }
+        if self.open_file(cx, path).is_err() {
+            return;
+        }
+        if let Some(range) = launch.range.as_ref() {
+            self.select_time(cx, range);
+        }
+        // without seek start from the range
+        if let Some(time) = launch.seek.or(launch.range.as_ref().map(|r| r.start)) {
+            self.seek_after_open(time);
+        }
+    }
+
+    /// editing state to save as project
+    pub fn project(&self, cx: &Context<Self>) -> Option<Project> {
+        let params = self.output_parames.read(cx);
+        let source = params.path.clone()?;
+        Some(Project {
+            source,
+            audio_ix: self.output_parames.read(cx).audio_stream_ix,
+            audio_mix: self.settings.read(cx).mix.clone(),
+            selection: self.range_time(),
+            ranges: params.ranges.clone(),
+            markers: params.markers.clone(),
+            export_profile: params.export_profile.clone(),
+            playhead: self.player.current_playtime() as f64,
+            ..Project::default()
+        })
+    }
+
+    /// open source of project and restore its ranges, rails and playhead
+    pub fn open_project(&mut self, cx: &mut Context<Self>, project: &Project, source: &PathBuf) {
+        if self.open_file(cx, source).is_err() {
+            return;
+        }
+        self.settings.update(cx, |s, cx| {
+            if let Some(ix) = project.audio_ix
+                && s.audio_rails.iter().any(|r| r.ix == ix)
+            {
+                s.audio_ix = ix;
+            }
+            s.mix = project
+                .audio_mix
+                .iter()
+                .filter(|m| s.audio_rails.iter().any(|r| r.ix == m.ix))
+                .copied()
+                .collect();
+            cx.notify();
+        });
+        self.output_parames.update(cx, |p, _| {
+            p.ranges = project.ranges.clone();
+            p.markers = project.markers.clone();
+            p.export_profile = project.export_profile.clone();
+        });
+        if let Some(selection) = project.selection.as_ref() {
+            self.select_time(cx, selection);
+        }
+        self.seek_after_open(project.playhead);
+    }
+
+    /// set A/B selection from seconds
+    fn select_time(&mut self, cx: &mut Context<Self>, range: &Range<f64>) {
+        let Some(dur) = self.player.duration_sec().filter(|d| *d > 0.) else {
+            return;
+        };
+        let start = (range.start / dur).clamp(0., 1.) as f32;
+        let end = (range.end / dur).clamp(0., 1.) as f32;
+        self.update_range(cx, (Some(start), Some(end)));
+    }
+
+    /// the player reopens for the audio rail right after open, seek is kept for it
+    fn seek_after_open(&mut self, time: f64) {
+        self.player.seek_player(|_, _| time);
+        self.pending_seek = Some(time);
+    }
+
+    /// extract thumbnails of file in background for the timeline
+    fn open_filmstrip(&mut self, cx: &mut Context<Self>, path: &PathBuf) {
+        let Some(video_ix) = self.output_parames.read(cx).video_stream_ix else {
+            self.filmstrip = None;
+            return;
+        };
+        let (filmstrip, thumbnails) = Filmstrip::open(path, video_ix);
+        self.filmstrip = Some(filmstrip);
+        let path = path.clone();
+        cx.spawn(async move |this, cx| {
+            while let Ok(thumbnail) = thumbnails.recv().await {
+                let kept = this.update(cx, |this, cx| {
+                    // another file was opened meanwhile
+                    if this.opened.as_ref() != Some(&path) {
+                        return false;
+                    }
+                    if let Some(filmstrip) = this.filmstrip.as_mut() {
+                        filmstrip.insert(thumbnail);
+                    }
+                    cx.notify();
+                    true
+                });
+                if !matches!(kept, Ok(true)) {
+                    break;
+                }
+            }
+        })
+        .detach();
+    }
+
+    /// analyze loudness of audio rail in background, kept while the rail stays
+    fn open_waveform(&mut self, cx: &mut Context<Self>, path: &PathBuf, ix: usize) {
+        if self.waveform.as_ref().is_some_and(|w| w.ix == ix) {
+            return;
+        }
+        let waveform = Waveform::open(path, ix);
+        let chunks = waveform.chunks();
+        self.waveform = Some(waveform);
+        cx.spawn(async move |this, cx| {
+            while let Ok(buckets) = chunks.recv().await {
+                let kept = this.update(cx, |this, cx| {
+                    // file closed or another rail picked meanwhile
+                    let Some(waveform) = this.waveform.as_mut().filter(|w| w.is_fed_by(&chunks))
+                    else {
+                        return false;
+                    };
+                    waveform.extend(buckets);
+                    cx.notify();
+                    true
+                });
+                if !matches!(kept, Ok(true)) {
+                    break;
+                }
+            }
+        })
+        .detach();
+    }
+
+    /// keep playhead and selection of the opened file in recent files
+    fn remember_position(&self, cx: &mut App) {
+        let Some(path) = self.opened.clone() else {
+            return;
+        };
+        let file = RecentFile {
+            path,
+            position: self.player.current_playtime() as f64,
+            selection: self.range_time(),
+        };
+        cx.update_global(|g: &mut AppConfig, _| {
+            g.remember_file(file);
+            g.save();
+        });
     }
 
     /// close file and reset player
     pub fn close_file(&mut self, cx: &mut Context<Self>) {
+        self.remember_position(cx);
+        self.opened = None;
+        self.timeline_viewport = TimelineViewport::default();
+        self.filmstrip = None;
+        self.waveform = None;
+        self.pending_seek = None;
         self.selection_range = Range {
             start: None,
             end: None,
         };
         self.output_parames.update(cx, |p, _| {
             p.selected_range = None;
+            p.ranges.clear();
+            p.markers.clear();
         });
         self.player = Player::new(self.size.clone(), self.output_parames.clone());
     }
 
-    /// close file and reset player
-    pub fn clear_selection(&mut self, _: &mut Context<Self>) {
+    /// clear the A/B selection
+    pub fn clear_selection(&mut self, cx: &mut Context<Self>) {
         self.selection_range = Range {
             start: None,
             end: None,
         };
+        self.output_parames.update(cx, |p, _| {
+            p.selected_range = None;
+        });
+    }
+
+    /// move current selection into range list
+    pub fn add_range(&mut self, cx: &mut Context<Self>) {
+        let Some(range) = self.range_time() else {
+            return;
+        };
+        self.output_parames.update(cx, |p, _| {
+            p.ranges.push(ClipRange::new(range));
+        });
+        self.clear_selection(cx);
+    }
+
+    /// remove the listed range under playhead
+    pub fn remove_range(&mut self, cx: &mut Context<Self>) {
+        let now = self.player.current_playtime() as f64;
+        self.output_parames.update(cx, |p, _| {
+            if let Some(ix) = p.ranges.iter().position(|r| r.range().contains(&now)) {
+                p.ranges.remove(ix);
+            }
+        });
+    }
+
+    /// drop a marker at playhead
+    pub fn add_marker(&mut self, cx: &mut Context<Self>) {
+        if self.player.get_state() == PlayState::Stopped {
+            return;
+        }
+        let time = self.player.current_playtime() as f64;
+        let name = t!(
+            "markers.default_name",
+            index = self.output_parames.read(cx).markers.len() + 1
+        )
+        .to_string();
+        self.output_parames.update(cx, |p, _| {
+            p.add_marker(Marker {
+                name: name.clone(),
+                time,
+            });
+        });
+        self.show_message(cx, name, None, Some(Duration::from_secs(1)));
+    }
+
+    /// seek to the marker after or before playhead
+    pub fn jump_marker(&mut self, cx: &mut Context<Self>, forward: bool) {
+        let now = self.player.current_playtime() as f64;
+        let params = self.output_parames.read(cx);
+        let marker = if forward {
+            params.next_marker(now)
+        } else {
+            params.prev_marker(now)
+        };
+        if let Some(time) = marker.map(|m| m.time) {
+            self.player.seek_player(|_, _| time);
+        }
     }
 
-    /// reselect audio rail
+    /// reselect audio rail, or rails of the mix
     fn reselect_rail(&mut self, cx: &mut Context<Self>, ix: usize) {
         // save current time
         self.player.pause_play();
         let time = self.player.current_playtime();
-        // reset decoder
-        self.close_file(cx);
+        // reset decoder, selection, ranges and rate stay
+        let rate = self.player.get_rate();
+        self.player = Player::new(self.size.clone(), self.output_parames.clone());
+        self.player.set_rate(rate);
+        let mix = self.settings.read(cx).mix.clone();
         if let Some(p) = self.output_parames.read(cx).path.clone() {
-            self.player.open(cx, &p).unwrap();
-            self.player.start_play(cx, Some(ix));
+            if let Err(e) = self.player.open(cx, &p) {
+                eprintln!("failed to open {:?}: {}", p, e);
+                return;
+            }
+            self.player.start_play(cx, Some(ix), &mix);
+            if self.output_parames.read(cx).audio_stream_ix.is_some() {
+                self.open_waveform(cx, &p, ix);
+            }
         }
+        self.output_parames.update(cx, |p, _| {
+            p.audio_mix = mix;
+        });
         // back to time before
-        if self.player.get_state() == PlayState::Playing {
+        if let Some(time) = self.pending_seek.take() {
+            self.player.seek_player(|_, _| time);
+        } else if self.player.get_state() == PlayState::Playing {
             self.player.seek_to(time);
         }
     }
@@ -164,6 +486,58 @@ impl MyApp {
         None
     }
 
+    /// listed ranges as percentage of progress
+    fn ranges_percent(&self, cx: &Context<Self>) -> Vec<Range<f32>> {
+        let Some(dur) = self.player.duration_sec() else {
+            return vec![];
+        };
+        self.output_parames
+            .read(cx)
+            .ranges
+            .iter()
+            .map(|r| (r.start / dur) as f32..(r.end / dur) as f32)
+            .collect()
+    }
+
+    /// move what is held on timeline to percent
+    fn drag_timeline(&mut self, cx: &mut Context<Self>, target: DragTarget, pct: f32) {
+        self.timeline_drag = Some(target);
+        match target {
+            DragTarget::Playhead => {
+                let dur = self.player.duration_sec().unwrap_or(0.);
+                self.player.scrub_to(dur * pct as f64);
+            }
+            // handles do not pass each other
+            DragTarget::RangeStart => {
+                let end = self.selection_range.end.unwrap_or(1.);
+                self.update_range(cx, (Some(pct.min(end)), None));
+            }
+            DragTarget::RangeEnd => {
+                let start = self.selection_range.start.unwrap_or(0.);
+                self.update_range(cx, (None, Some(pct.max(start))));
+            }
+        }
+    }
+
+    fn drop_timeline(&mut self) {
+        if self.timeline_drag.take() == Some(DragTarget::Playhead) {
+            self.player.finish_scrub();
+        }
+    }
+
+    /// markers as percentage of progress
+    fn markers_percent(&self, cx: &Context<Self>) -> Vec<f32> {
+        let Some(dur) = self.player.duration_sec() else {
+            return vec![];
+        };
+        self.output_parames
+            .read(cx)
+            .markers
+            .iter()
+            .map(|m| (m.time / dur) as f32)
+            .collect()
+    }
+
     /// set and update range
     fn update_range(&mut self, cx: &mut Context<Self>, percent_range: (Option<f32>, Option<f32>)) {
         if let Some(a) = percent_range.0 {
@@ -220,11 +594,60 @@ impl MyApp {
         );
     }
 
+    /// J/K/L shuttle, one rate step slower or faster, starts playing when paused
+    fn shuttle(&mut self, cx: &mut Context<Self>, faster: bool) {
+        let state = self.player.get_state();
+        if state == PlayState::Stopped {
+            return;
+        }
+        let rate = match state {
+            PlayState::Paused => 1.0,
+            _ => self.player.get_rate(),
+        };
+        let next = if faster && state == PlayState::Paused {
+            Some(rate)
+        } else if faster {
+            PLAYBACK_RATES.iter().find(|r| **r > rate).copied()
+        } else {
+            PLAYBACK_RATES.iter().rev().find(|r| **r < rate).copied()
+        };
+        if state == PlayState::Paused {
+            self.player.resume_play();
+        }
+        self.player.set_rate(next.unwrap_or(rate));
+        self.show_rate(cx);
+    }
+
+    fn show_rate(&mut self, cx: &mut Context<Self>) {
+        self.show_message(
+            cx,
+            format!("{}x", self.player.get_rate()),
+            Some(rounded::FAST_FOWARD.into()),
+            Some(Duration::from_secs(2)),
+        );
+    }
+
+    /// video frame, or waveform around playhead for audio only files
+    fn preview(&mut self, window: &mut Window, cx: &Context<Self>) -> AnyElement {
+        let viewer = self.player.view(window);
+        if self.opened.is_none() || self.output_parames.read(cx).video_stream_ix.is_some() {
+            return viewer.into_any_element();
+        }
+        let time = self.player.current_playtime();
+        let visible = time - WAVE_VIEW_SEC / 2.0..time + WAVE_VIEW_SEC / 2.0;
+        let columns = self
+            .waveform
+            .as_ref()
+            .map(|w| w.columns(visible, WAVEFORM_COLUMNS))
+            .unwrap_or_default();
+        WaveViewer::new(columns).into_any_element()
+    }
+
     /// listen open file event
     fn listen_open(params: &Entity<OutputParams>, cx: &mut Context<Self>) {
         cx.observe(params, |this, e: Entity<OutputParams>, cx| {
             if let Some(path) = e.read(cx).path.clone() {
-                this.open_file(cx, &path);
+                let _ = this.open_file(cx, &path);
             }
         })
         .detach();
@@ -254,6 +677,9 @@ impl Render for MyApp {
                 cx.notify();
             });
         }
+        if self.player.get_state() == PlayState::Playing {
+            self.timeline_viewport.follow(self.play_percent());
+        }
         if matches!(self.message_mgr, MessageState::None)
             || matches!(self.message_mgr, MessageState::Seeking)
         {
@@ -286,16 +712,30 @@ impl Render for MyApp {
                     .track_focus(&self.focus_handle)
                     .on_action(cx.listener(on_close_file))
                     .on_action(cx.listener(on_clear_selection))
+                    .on_action(cx.listener(on_add_range))
+                    .on_action(cx.listener(on_remove_range))
+                    .on_action(cx.listener(on_add_marker))
+                    .on_action(cx.listener(on_prev_marker))
+                    .on_action(cx.listener(on_next_marker))
+                    .on_action(cx.listener(on_markers))
+                    .on_action(cx.listener(on_audio_mixer))
+                    .on_action(cx.listener(on_save_project))
+                    .on_action(cx.listener(on_open_project))
                     .on_action(cx.listener(on_switch))
                     .on_action(cx.listener(on_back))
                     .on_action(cx.listener(on_foward))
+                    .on_action(cx.listener(on_shuttle_back))
+                    .on_action(cx.listener(on_shuttle_stop))
+                    .on_action(cx.listener(on_shuttle_forward))
+                    .on_action(cx.listener(on_step_back))
+                    .on_action(cx.listener(on_step_forward))
                     .on_action(cx.listener(on_set_start))
                     .on_action(cx.listener(on_set_end))
                     .on_action(cx.listener(on_vol_up))
                     .on_action(cx.listener(on_vol_down))
                     .on_drop(cx.listener(|this, e: &ExternalPaths, _, cx| {
                         if let Some(path) = e.paths().first() {
-                            this.open_file(cx, path);
+                            let _ = this.open_file(cx, path);
                         }
                     }))
                     .v_flex()
@@ -309,7 +749,7 @@ impl Render for MyApp {
                             .items_center()
                             .size_full()
                             .bg(bg_color)
-                            .child(self.player.view(window))
+                            .child(self.preview(window, cx))
                             .when_some(self.message.clone(), |this, msg| {
                                 this.child(message_box(msg, self.message_icon.clone()))
                             }),
@@ -328,7 +768,33 @@ impl Render for MyApp {
 fn control_area(this: &mut MyApp, cx: &mut Context<MyApp>) -> AnyElement {
     let play_state = this.player.get_state();
     let weak = cx.weak_entity();
+    let weak_scroll = weak.clone();
+    let weak_drop = weak.clone();
+    let duration = this.player.duration_sec().unwrap_or(0.);
+    let viewport = this.timeline_viewport;
+    let thumbnails = match this.filmstrip.as_mut() {
+        Some(filmstrip) if duration > 0. => {
+            let visible = viewport.start as f64 * duration..viewport.end() as f64 * duration;
+            filmstrip.request(duration, visible);
+            filmstrip
+                .visible()
+                .into_iter()
+                .map(|(time, image)| ((time / duration) as f32, image))
+                .collect()
+        }
+        _ => vec![],
+    };
+    let waveform = this
+        .waveform
+        .as_ref()
+        .filter(|_| duration > 0.)
+        .map(|w| {
+            let visible = viewport.start as f64 * duration..viewport.end() as f64 * duration;
+            w.columns(visible, WAVEFORM_COLUMNS)
+        })
+        .unwrap_or_default();
     let bg_color = cx.theme().background;
+    let listed = this.output_parames.read(cx).ranges.clone();
 
     div()
         .w_full()
@@ -342,11 +808,34 @@ fn control_area(this: &mut MyApp, cx: &mut Context<MyApp>) -> AnyElement {
                     this.play_percent(),
                     this.selection_range.clone(),
                 )
-                .on_click(move |pct, cx| {
-                    weak.update(cx, |this, _| {
-                        this.player.seek_player(|_, dur| dur * pct as f64);
+                .ranges(this.ranges_percent(cx))
+                .markers(this.markers_percent(cx))
+                .viewport(viewport, duration)
+                .thumbnails(thumbnails)
+                .waveform(waveform)
+                .on_scroll(move |scroll, cx| {
+                    weak_scroll
+                        .update(cx, |this, cx| {
+                            this.timeline_viewport.scroll(scroll, duration);
+                            cx.notify();
+                        })
+                        .unwrap();
+                })
+                .dragging(this.timeline_drag)
+                .on_drag(move |target, pct, cx| {
+                    weak.update(cx, |this, cx| {
+                        this.drag_timeline(cx, target, pct);
+                        cx.notify();
                     })
                     .unwrap();
+                })
+                .on_drop(move |cx| {
+                    weak_drop
+                        .update(cx, |this, cx| {
+                            this.drop_timeline();
+                            cx.notify();
+                        })
+                        .unwrap();
                 }),
             ),
         )
@@ -443,6 +932,20 @@ fn control_area(this: &mut MyApp, cx: &mut Context<MyApp>) -> AnyElement {
                     div()
                         .h_flex()
                         .gap_2()
+                        .when(!listed.is_empty(), |d| {
+                            d.child(
+                                Chip::new()
+                                    .color(rgba(0x2f8f5acc))
+                                    .border()
+                                    .label(format!(
+                                        "{} × {}",
+                                        listed.len(),
+                                        format_sec(listed.iter().map(|r| r.end - r.start).sum())
+                                    ))
+                                    .bold()
+                                    .mono(),
+                            )
+                        })
                         .when_some(this.range_time(), |d, time| {
                             d.child(
                                 Chip::new()
@@ -515,6 +1018,141 @@ fn on_clear_selection(
     this.clear_selection(cx);
     cx.notify();
 }
+fn on_add_range(this: &mut MyApp, _: &AddRange, _: &mut Window, cx: &mut Context<MyApp>) {
+    this.add_range(cx);
+    cx.notify();
+}
+fn on_remove_range(this: &mut MyApp, _: &RemoveRange, _: &mut Window, cx: &mut Context<MyApp>) {
+    this.remove_range(cx);
+    cx.notify();
+}
+fn on_add_marker(this: &mut MyApp, _: &AddMarker, _: &mut Window, cx: &mut Context<MyApp>) {
+    this.add_marker(cx);
+    cx.notify();
+}
+fn on_prev_marker(this: &mut MyApp, _: &PrevMarker, _: &mut Window, cx: &mut Context<MyApp>) {
+    this.jump_marker(cx, false);
+    cx.notify();
+}
+fn on_next_marker(this: &mut MyApp, _: &NextMarker, _: &mut Window, cx: &mut Context<MyApp>) {
+    this.jump_marker(cx, true);
+    cx.notify();
+}
+fn on_markers(this: &mut MyApp, _: &Markers, window: &mut Window, cx: &mut Context<MyApp>) {
+    if this.output_parames.read(cx).markers.is_empty() || window.has_active_dialog(cx) {
+        return;
+    }
+    let params = this.output_parames.clone();
+    let view = cx.new(|cx| MarkersView::new(window, cx, params));
+    window.open_dialog(cx, move |dialog, _, _| {
+        build_markers_dialog(dialog, view.clone())
+    });
+}
+fn on_audio_mixer(this: &mut MyApp, _: &AudioMixer, window: &mut Window, cx: &mut Context<MyApp>) {
+    if this.settings.read(cx).audio_rails.is_empty() || window.has_active_dialog(cx) {
+        return;
+    }
+    let settings = this.settings.clone();
+    let view = cx.new(|cx| MixerView::new(window, cx, settings));
+    window.open_dialog(cx, move |dialog, _, _| {
+        build_mixer_dialog(dialog, view.clone())
+    });
+}
+fn on_save_project(
+    this: &mut MyApp,
+    _: &SaveProject,
+    window: &mut Window,
+    cx: &mut Context<MyApp>,
+) {
+    let Some(project) = this.project(cx) else {
+        return;
+    };
+    let target = project_path_for(&project.source);
+    let directory = target.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+    let file_name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let result = cx.prompt_for_new_path(&directory, Some(file_name.as_str()));
+    let window_handle = window.window_handle();
+    cx.spawn(async move |_, cx| {
+        let Ok(Ok(Some(path))) = result.await else {
+            return;
+        };
+        let notification = match project.save(&path) {
+            Ok(()) => Notification::success(t!("project.saved")),
+            Err(e) => Notification::error(t!("project.failed", error = e.to_string())),
+        };
+        let _ = cx.update_window(window_handle, |_, w, cx| {
+            w.push_notification(notification.w(px(260.)), cx);
+        });
+    })
+    .detach();
+}
+fn on_open_project(_: &mut MyApp, _: &OpenProject, window: &mut Window, cx: &mut Context<MyApp>) {
+    let result = cx.prompt_for_paths(gpui::PathPromptOptions {
+        files: true,
+        directories: false,
+        multiple: false,
+        prompt: Some(t!("menu.file.open_project").into()),
+    });
+    let window_handle = window.window_handle();
+    cx.spawn(async move |this, cx| {
+        let Ok(Ok(Some(paths))) = result.await else {
+            return;
+        };
+        let Some(path) = paths
+            .first()
+            .filter(|p| p.extension().is_some_and(|ext| ext == PROJECT_EXTENSION))
+        else {
+            return;
+        };
+        let project = match Project::load(path) {
+            Ok(project) => project,
+            Err(e) => {
+                let notification = Notification::error(t!("project.failed", error = e.to_string()));
+                let _ = cx.update_window(window_handle, |_, w, cx| {
+                    w.push_notification(notification.w(px(260.)), cx);
+                });
+                return;
+            }
+        };
+
+        let source = match project.locate_source(path) {
+            Some(source) => source,
+            None => {
+                // relink the moved source by hand
+                let name = project
+                    .source
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let Ok(relink) = this.update(cx, |_, cx| {
+                    cx.prompt_for_paths(gpui::PathPromptOptions {
+                        files: true,
+                        directories: false,
+                        multiple: false,
+                        prompt: Some(t!("project.relink", name = name).into()),
+                    })
+                }) else {
+                    return;
+                };
+                let Ok(Ok(Some(paths))) = relink.await else {
+                    return;
+                };
+                let Some(source) = paths.first().cloned() else {
+                    return;
+                };
+                source
+            }
+        };
+        let _ = this.update(cx, |this, cx| {
+            this.open_project(cx, &project, &source);
+            cx.notify();
+        });
+    })
+    .detach();
+}
 fn on_close_file(this: &mut MyApp, _: &Close, _: &mut Window, cx: &mut Context<MyApp>) {
     this.close_file(cx);
     cx.notify();
@@ -539,6 +1177,39 @@ fn on_foward(this: &mut MyApp, _: &Forward, _: &mut Window, cx: &mut Context<MyA
         .seek_player(|now, duration| config.handle_seek(now, duration, true));
     cx.notify();
 }
+fn on_shuttle_back(this: &mut MyApp, _: &ShuttleBack, _: &mut Window, cx: &mut Context<MyApp>) {
+    this.shuttle(cx, false);
+    cx.notify();
+}
+fn on_shuttle_stop(this: &mut MyApp, _: &ShuttleStop, _: &mut Window, cx: &mut Context<MyApp>) {
+    if this.player.get_state() == PlayState::Playing {
+        this.player.pause_play();
+    }
+    this.player.set_rate(1.0);
+    cx.notify();
+}
+fn on_shuttle_forward(
+    this: &mut MyApp,
+    _: &ShuttleForward,
+    _: &mut Window,
+    cx: &mut Context<MyApp>,
+) {
+    this.shuttle(cx, true);
+    cx.notify();
+}
+fn on_step_back(this: &mut MyApp, _: &StepBack, window: &mut Window, cx: &mut Context<MyApp>) {
+    this.player.step_back(window);
+    cx.notify();
+}
+fn on_step_forward(
+    this: &mut MyApp,
+    _: &StepForward,
+    window: &mut Window,
+    cx: &mut Context<MyApp>,
+) {
+    this.player.step_forward(window);
+    cx.notify();
+}
 
 fn on_set_start(this: &mut MyApp, _: &SetStart, _: &mut Window, cx: &mut Context<MyApp>) {
     if this.player.get_state() != PlayState::Stopped {
@@ -565,16 +1236,6 @@ fn on_vol_down(this: &mut MyApp, _: &VolumeDown, _: &mut Window, cx: &mut Contex
     cx.notify();
 }
 
-fn format_sec(sec: f64) -> String {
-    let millis = (sec.max(0.0) * 1_000.0).floor() as u64;
-    format!(
-        "{:02}:{:02}.{:02}",
-        millis / 60_000,
-        millis / 1_000 % 60,
-        millis % 100,
-    )
-}
-
 impl Focusable for MyApp {
     fn focus_handle(&self, _: &gpui::App) -> gpui::FocusHandle {
         self.focus_handle.clone()
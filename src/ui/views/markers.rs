@@ -0,0 +1,171 @@
+use gpui::{App, AppContext, Context, Entity, ParentElement, Render, Styled, Window, div, px};
+use gpui_component::{
+    Disableable, IconName, Sizable, StyledExt, WindowExt,
+    button::{Button, ButtonVariants},
+    checkbox::Checkbox,
+    dialog::Dialog,
+    input::{Input, InputState},
+    label::Label,
+};
+use rust_i18n::t;
+
+use crate::{
+    models::model::{ClipRange, Marker, OutputParams},
+    ui::player::utils::format_sec,
+};
+
+pub struct MarkersView {
+    params: Entity<OutputParams>,
+    // edited copy, written back on confirm
+    markers: Vec<Marker>,
+    name_inputs: Vec<Entity<InputState>>,
+    // index of markers picked to make a range
+    picked: Vec<usize>,
+}
+
+impl MarkersView {
+    pub fn new(window: &mut Window, cx: &mut Context<Self>, params: Entity<OutputParams>) -> Self {
+        let markers = params.read(cx).markers.clone();
+        let name_inputs = markers
+            .iter()
+            .map(|m| {
+                let name = m.name.clone();
+                cx.new(|cx| InputState::new(window, cx).default_value(name))
+            })
+            .collect();
+
+        Self {
+            params,
+            markers,
+            name_inputs,
+            picked: vec![],
+        }
+    }
+
+    fn toggle(&mut self, ix: usize, checked: bool, cx: &mut Context<Self>) {
+        if checked {
+            if !self.picked.contains(&ix) {
+                self.picked.push(ix);
+            }
+        } else {
+            self.picked.retain(|i| *i != ix);
+        }
+        cx.notify();
+    }
+
+    fn remove(&mut self, ix: usize, cx: &mut Context<Self>) {
+        self.markers.remove(ix);
+        self.name_inputs.remove(ix);
+        self.picked.clear();
+        cx.notify();
+    }
+
+    /// add the two picked markers as a range
+    fn add_range(&mut self, cx: &mut Context<Self>) {
+        let [a, b] = self.picked[..] else {
+            return;
+        };
+        let (a, b) = (self.markers[a].time, self.markers[b].time);
+        if a == b {
+            return;
+        }
+        self.params.update(cx, |p, _| {
+            p.ranges.push(ClipRange::new(a.min(b)..a.max(b)));
+        });
+        self.picked.clear();
+        cx.notify();
+    }
+
+    /// keep renamed and removed markers
+    fn apply(&self, cx: &mut App) {
+        let markers: Vec<Marker> = self
+            .markers
+            .iter()
+            .zip(self.name_inputs.iter())
+            .map(|(m, input)| Marker {
+                name: input.read(cx).value().trim().to_string(),
+                time: m.time,
+            })
+            .collect();
+        self.params.update(cx, |p, _| {
+            p.markers = markers;
+        });
+    }
+}
+
+impl Render for MarkersView {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl gpui::IntoElement {
+        div()
+            .w_full()
+            .v_flex()
+            .gap_2()
+            .child(Label::new(t!("markers.description")))
+            .children(
+                self.markers
+                    .iter()
+                    .zip(self.name_inputs.iter())
+                    .enumerate()
+                    .map(|(i, (marker, input))| {
+                        div()
+                            .h_flex()
+                            .justify_between()
+                            .gap_3()
+                            .child(
+                                Checkbox::new(("marker-pick", i))
+                                    .label(format_sec(marker.time))
+                                    .checked(self.picked.contains(&i))
+                                    .on_click(cx.listener(move |this, checked: &bool, _, cx| {
+                                        this.toggle(i, *checked, cx);
+                                    })),
+                            )
+                            .child(
+                                div()
+                                    .h_flex()
+                                    .gap_1()
+                                    .child(div().w(px(200.)).child(Input::new(input)))
+                                    .child(
+                                        Button::new(("marker-remove", i))
+                                            .ghost()
+                                            .small()
+                                            .icon(IconName::Close)
+                                            .on_click(cx.listener(move |this, _, _, cx| {
+                                                this.remove(i, cx);
+                                            })),
+                                    ),
+                            )
+                    }),
+            )
+            .child(
+                div().h_flex().justify_end().child(
+                    Button::new("marker-range")
+                        .label(t!("markers.add_range"))
+                        .disabled(self.picked.len() != 2)
+                        .on_click(cx.listener(|this, _, _, cx| this.add_range(cx))),
+                ),
+            )
+    }
+}
+
+pub fn build_markers_dialog(dialog: Dialog, view: Entity<MarkersView>) -> Dialog {
+    let view_for_action = view.clone();
+    dialog.title(t!("markers.title")).child(view).footer(
+        div()
+            .h_flex()
+            .justify_end()
+            .gap_2()
+            .child(
+                Button::new("cancel")
+                    .label(t!("common.actions.cancel"))
+                    .on_click(|_, window, cx| window.close_dialog(cx)),
+            )
+            .child(
+                Button::new("apply")
+                    .primary()
+                    .label(t!("common.actions.confirm"))
+                    .on_click(move |_, window, cx| {
+                        view_for_action.update(cx, |view, cx| view.apply(cx));
+                        window.close_dialog(cx);
+                    }),
+            ),
+    )
+}
@@ -0,0 +1,181 @@
+use gpui::{App, AppContext, Context, Entity, ParentElement, Render, Styled, Window, div, px};
+use gpui_component::{
+    StyledExt, WindowExt,
+    button::{Button, ButtonVariants},
+    checkbox::Checkbox,
+    dialog::Dialog,
+    input::{Input, InputState},
+    label::Label,
+    select::SelectItem,
+};
+use rust_i18n::t;
+
+use crate::ui::player::{
+    model::{AudioRail, MixTrack},
+    settings::PlayerSettings,
+};
+
+/// highest gain of a mixed track, in percent
+static MAX_GAIN: f32 = 200.;
+
+pub struct MixerView {
+    settings: Entity<PlayerSettings>,
+    rails: Vec<AudioRail>,
+    // stream index of every rail to play
+    selected: Vec<usize>,
+    // gain in percent, same order as rails
+    gain_inputs: Vec<Entity<InputState>>,
+}
+
+impl MixerView {
+    pub fn new(
+        window: &mut Window,
+        cx: &mut Context<Self>,
+        settings: Entity<PlayerSettings>,
+    ) -> Self {
+        let s = settings.read(cx);
+        let rails = s.audio_rails.clone();
+        let mix = s.mix.clone();
+        let selected = if mix.is_empty() {
+            vec![s.audio_ix]
+        } else {
+            mix.iter().map(|m| m.ix).collect()
+        };
+        let gain_inputs = rails
+            .iter()
+            .map(|r| {
+                let gain = mix.iter().find(|m| m.ix == r.ix).map_or(1.0, |m| m.gain);
+                gain_input(gain, window, cx)
+            })
+            .collect();
+
+        Self {
+            settings,
+            rails,
+            selected,
+            gain_inputs,
+        }
+    }
+
+    /// selected rails in source order with their gains
+    fn mix(&self, cx: &App) -> Vec<MixTrack> {
+        self.rails
+            .iter()
+            .zip(self.gain_inputs.iter())
+            .filter(|(r, _)| self.selected.contains(&r.ix))
+            .map(|(r, input)| MixTrack {
+                ix: r.ix,
+                gain: parse_gain(input.read(cx)),
+            })
+            .collect()
+    }
+
+    /// play the mix, the player reopens the file on settings change
+    fn apply(&self, cx: &mut App) {
+        let mix = self.mix(cx);
+        let Some(first) = mix.first().copied() else {
+            return;
+        };
+        self.settings.update(cx, |s, cx| {
+            if !mix.iter().any(|m| m.ix == s.audio_ix) {
+                s.audio_ix = first.ix;
+            }
+            s.mix = mix;
+            cx.notify();
+        });
+    }
+
+    fn toggle(&mut self, ix: usize, checked: bool, cx: &mut Context<Self>) {
+        if checked {
+            if !self.selected.contains(&ix) {
+                self.selected.push(ix);
+            }
+        } else {
+            self.selected.retain(|i| *i != ix);
+        }
+        cx.notify();
+    }
+}
+
+impl Render for MixerView {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl gpui::IntoElement {
+        let only_one = self.selected.len() == 1;
+        div()
+            .w_full()
+            .v_flex()
+            .gap_2()
+            .child(Label::new(t!("mixer.description")))
+            .children(
+                self.rails
+                    .iter()
+                    .zip(self.gain_inputs.iter())
+                    .map(|(rail, input)| {
+                        let ix = rail.ix;
+                        let checked = self.selected.contains(&ix);
+                        div()
+                            .h_flex()
+                            .justify_between()
+                            .gap_3()
+                            .child(
+                                Checkbox::new(("mix-track", ix))
+                                    .label(rail.title())
+                                    .checked(checked)
+                                    // at least one track is played
+                                    .disabled(checked && only_one)
+                                    .on_click(cx.listener(move |this, checked: &bool, _, cx| {
+                                        this.toggle(ix, *checked, cx);
+                                    })),
+                            )
+                            .child(gain_field(input))
+                    }),
+            )
+    }
+}
+
+/// text input of gain in percent
+pub fn gain_input<T>(gain: f32, window: &mut Window, cx: &mut Context<T>) -> Entity<InputState> {
+    let value = format!("{:.0}", gain * 100.);
+    cx.new(|cx| InputState::new(window, cx).default_value(value))
+}
+
+/// gain from percent input, invalid text means unchanged volume
+pub fn parse_gain(input: &InputState) -> f32 {
+    input
+        .value()
+        .trim()
+        .trim_end_matches('%')
+        .parse::<f32>()
+        .map_or(1.0, |percent| percent.clamp(0., MAX_GAIN) / 100.)
+}
+
+pub fn gain_field(input: &Entity<InputState>) -> impl gpui::IntoElement {
+    div()
+        .h_flex()
+        .gap_1()
+        .child(div().w(px(70.)).child(Input::new(input)))
+        .child(Label::new("%"))
+}
+
+pub fn build_mixer_dialog(dialog: Dialog, view: Entity<MixerView>) -> Dialog {
+    let view_for_action = view.clone();
+    dialog.title(t!("mixer.title")).child(view).footer(
+        div()
+            .h_flex()
+            .justify_end()
+            .gap_2()
+            .child(
+                Button::new("cancel")
+                    .label(t!("common.actions.cancel"))
+                    .on_click(|_, window, cx| window.close_dialog(cx)),
+            )
+            .child(
+                Button::new("apply")
+                    .primary()
+                    .label(t!("common.actions.confirm"))
+                    .on_click(move |_, window, cx| {
+                        view_for_action.update(cx, |view, cx| view.apply(cx));
+                        window.close_dialog(cx);
+                    }),
+            ),
+    )
+}
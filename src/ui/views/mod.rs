@@ -1,4 +1,6 @@
 pub mod about;
 pub mod app;
+pub mod markers;
+pub mod mixer;
 pub mod output;
 pub mod settings;
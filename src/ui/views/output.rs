@@ -1,56 +1,112 @@
 use std::{
     ffi::OsStr,
-    ops::Range,
     path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    },
 };
 
 use gpui::{
-    App, AppContext, ClickEvent, Context, Entity, ParentElement, Render, Styled, Window, div, px,
+    App, AppContext, BorrowAppContext, ClickEvent, Context, Entity, ParentElement, Render,
+    SharedString, Styled, Window, div, prelude::FluentBuilder, px,
 };
 use gpui_component::{
-    Disableable, IndexPath, StyledExt, WindowExt,
+    Disableable, IconName, IndexPath, Sizable, StyledExt, WindowExt,
     button::{Button, ButtonVariants},
     checkbox::Checkbox,
     dialog::Dialog,
-    input::{Input, InputState},
+    input::{Input, InputEvent, InputState},
     label::Label,
     notification::Notification,
-    select::{Select, SelectState},
+    progress::Progress,
+    select::{Select, SelectEvent, SelectItem, SelectState},
 };
 use path_absolutize::Absolutize;
 use rust_i18n::t;
+use strum::IntoEnumIterator;
 
 use crate::{
-    models::model::OutputParams,
-    ui::{output::output::output, player::model::AudioRail},
+    config::{AppConfig, AudioCodec, Container, ExportProfile, RateControl, VideoCodec},
+    models::model::{ClipRange, OutputParams},
+    ui::{
+        output::{
+            output::OutputJob,
+            progress::{CancelToken, Cancelled},
+        },
+        player::{
+            model::{AudioRail, ExtraStream, StreamKind},
+            utils::format_sec,
+        },
+        views::mixer::{gain_field, gain_input, parse_gain},
+    },
 };
 
+static DEFAULT_TEMPLATE: &str = "{name}_{index}";
+
 pub struct OutputView {
     params: Entity<OutputParams>,
     input: Entity<InputState>,
     output_path: PathBuf,
-    audio_select: Entity<SelectState<Vec<AudioRail>>>,
+    audio_rails: Vec<AudioRail>,
+    // stream index of every audio track to export
+    audio_selected: Vec<usize>,
+    // mix selected tracks into one stream
+    mix_audio: bool,
+    // gain in percent, same order as audio_rails
+    gain_inputs: Vec<Entity<InputState>>,
+    extra_streams: Vec<ExtraStream>,
+    // kinds of extra streams copied into export
+    keep_kinds: Vec<StreamKind>,
     update_path: bool,
+    copy_stream: bool,
+    frame_accurate: bool,
+    strip_metadata: bool,
+    markers_as_chapters: bool,
+    rate_control: RateControl,
+    container_select: Entity<SelectState<Vec<Container>>>,
+    video_codec_select: Entity<SelectState<Vec<VideoCodec>>>,
+    audio_codec_select: Entity<SelectState<Vec<AudioCodec>>>,
+    rate_control_select: Entity<SelectState<Vec<RateControl>>>,
+    crf_input: Entity<InputState>,
+    video_bitrate_input: Entity<InputState>,
+    audio_bitrate_input: Entity<InputState>,
+    batch: bool,
+    template_input: Entity<InputState>,
+    // label of each listed range, same order as ranges of params
+    label_inputs: Vec<Entity<InputState>>,
     working: bool,
+    cancel: Option<CancelToken>,
+    // f32 bits of export progress, written by export thread
+    progress: Arc<AtomicU32>,
 }
 
 impl OutputView {
     pub fn new(
         window: &mut gpui::Window,
-        cx: &mut gpui::App,
+        cx: &mut Context<Self>,
         params: Entity<OutputParams>,
     ) -> Self {
         let p = params.read(cx);
-        let rails = p.audio_rails.clone().unwrap();
-        let list_ix = rails
-            .iter()
-            .position(|r| r.ix == p.audio_stream_ix.unwrap());
-        let selected_index = if let Some(ix) = list_ix {
-            Some(IndexPath::new(ix))
+        let audio_rails = p.audio_rails.clone().unwrap();
+        // follow the mix of player when several tracks are played
+        let audio_mix = p.audio_mix.clone();
+        let mix_audio = audio_mix.len() > 1;
+        let audio_selected = if mix_audio {
+            audio_mix.iter().map(|m| m.ix).collect()
         } else {
-            None
+            p.audio_stream_ix.into_iter().collect()
         };
-        let audio_select = cx.new(|cx| SelectState::new(rails, selected_index, window, cx));
+        let gain_inputs = audio_rails
+            .iter()
+            .map(|r| {
+                let gain = audio_mix
+                    .iter()
+                    .find(|m| m.ix == r.ix)
+                    .map_or(1.0, |m| m.gain);
+                gain_input(gain, window, cx)
+            })
+            .collect();
 
         let path = params.read(cx).path.clone().unwrap();
         let new_path = if let Some(stem) = path.file_stem() {
@@ -67,6 +123,14 @@ impl OutputView {
             path.with_file_name("output.mp4")
         };
 
+        let profile = params.read(cx).export_profile.clone();
+        // container of source keeps stream copy compatible, last used one otherwise
+        let container = new_path
+            .extension()
+            .and_then(|ext| Container::from_extension(&ext.to_string_lossy()))
+            .unwrap_or(profile.container);
+        let new_path = new_path.with_extension(container.extension());
+
         let default = new_path
             .absolutize()
             .unwrap()
@@ -74,29 +138,268 @@ impl OutputView {
             .into_owned();
 
         let input = cx.new(|cx| InputState::new(window, cx).default_value(default));
+
+        let container_select = enum_select(container, window, cx);
+        cx.subscribe(
+            &container_select,
+            |this, _, e: &SelectEvent<Vec<Container>>, cx| {
+                if let SelectEvent::Confirm(Some(value)) = e {
+                    this.output_path = this.output_path.with_extension(value.extension());
+                    this.update_path = true;
+                    cx.notify();
+                }
+            },
+        )
+        .detach();
+        let extra_streams = params.read(cx).extra_streams.clone();
+        let keep_kinds = [
+            (StreamKind::Subtitle, profile.keep_subtitles),
+            (StreamKind::Attachment, profile.keep_attachments),
+            (StreamKind::Data, profile.keep_data),
+        ]
+        .into_iter()
+        .filter_map(|(kind, keep)| keep.then_some(kind))
+        .collect();
+        let video_codec_select = enum_select(profile.video_codec, window, cx);
+        let audio_codec_select = enum_select(profile.audio_codec, window, cx);
+        let rate_control_select = enum_select(profile.rate_control, window, cx);
+        cx.subscribe(
+            &rate_control_select,
+            |this, _, e: &SelectEvent<Vec<RateControl>>, cx| {
+                if let SelectEvent::Confirm(Some(value)) = e {
+                    this.rate_control = *value;
+                    cx.notify();
+                }
+            },
+        )
+        .detach();
+        let crf_input = number_input(profile.crf, window, cx);
+        let video_bitrate_input = number_input(profile.video_bitrate, window, cx);
+        let audio_bitrate_input = number_input(profile.audio_bitrate, window, cx);
+        let template_input =
+            cx.new(|cx| InputState::new(window, cx).default_value(DEFAULT_TEMPLATE));
+        let ranges = params.read(cx).ranges.clone();
+        let label_inputs = ranges
+            .iter()
+            .map(|r| {
+                let label = r.label.clone();
+                let input = cx.new(|cx| {
+                    InputState::new(window, cx)
+                        .placeholder(t!("output.range_label").to_string())
+                        .default_value(label)
+                });
+                cx.subscribe(&input, |this, _, e: &InputEvent, cx| {
+                    if matches!(e, InputEvent::Change { .. }) {
+                        this.apply_labels(cx);
+                    }
+                })
+                .detach();
+                input
+            })
+            .collect();
+
         Self {
             params,
             input,
             output_path: new_path,
-            audio_select,
+            audio_rails,
+            audio_selected,
+            mix_audio,
+            gain_inputs,
+            extra_streams,
+            keep_kinds,
             update_path: false,
+            copy_stream: profile.copy_stream,
+            frame_accurate: profile.frame_accurate,
+            strip_metadata: profile.strip_metadata,
+            markers_as_chapters: profile.markers_as_chapters,
+            rate_control: profile.rate_control,
+            container_select,
+            video_codec_select,
+            audio_codec_select,
+            rate_control_select,
+            crf_input,
+            video_bitrate_input,
+            audio_bitrate_input,
+            batch: false,
+            template_input,
+            label_inputs,
             working: false,
+            cancel: None,
+            progress: Arc::new(AtomicU32::new(0)),
+        }
+    }
+
+    /// collect export settings from dialog, invalid numbers keep the last value
+    fn export_profile(&self, cx: &App) -> ExportProfile {
+        let mut profile = self.params.read(cx).export_profile.clone();
+        profile.copy_stream = self.copy_stream;
+        profile.frame_accurate = self.frame_accurate;
+        profile.strip_metadata = self.strip_metadata;
+        profile.markers_as_chapters = self.markers_as_chapters;
+        profile.rate_control = self.rate_control;
+        if let Some(container) = self.container_select.read(cx).selected_value() {
+            profile.container = *container;
+        }
+        profile.keep_subtitles = self.keep_kinds.contains(&StreamKind::Subtitle);
+        profile.keep_attachments = self.keep_kinds.contains(&StreamKind::Attachment);
+        profile.keep_data = self.keep_kinds.contains(&StreamKind::Data);
+        if let Some(codec) = self.video_codec_select.read(cx).selected_value() {
+            profile.video_codec = *codec;
+        }
+        if let Some(codec) = self.audio_codec_select.read(cx).selected_value() {
+            profile.audio_codec = *codec;
+        }
+        if let Ok(crf) = self.crf_input.read(cx).value().trim().parse::<u8>() {
+            profile.crf = crf.min(profile.video_codec.max_crf());
         }
+        if let Ok(rate) = self
+            .video_bitrate_input
+            .read(cx)
+            .value()
+            .trim()
+            .parse::<u32>()
+            && rate > 0
+        {
+            profile.video_bitrate = rate;
+        }
+        if let Ok(rate) = self
+            .audio_bitrate_input
+            .read(cx)
+            .value()
+            .trim()
+            .parse::<u32>()
+            && rate > 0
+        {
+            profile.audio_bitrate = rate;
+        }
+        profile
     }
 
-    fn output_job(&self, cx: &gpui::App) -> Option<(PathBuf, PathBuf, usize, usize, Range<f64>)> {
+    /// keep profile for next export and save it as last used profile
+    fn save_profile(&self, profile: &ExportProfile, cx: &mut Context<Self>) {
+        self.params.update(cx, |p, _| {
+            p.export_profile = profile.clone();
+        });
+        cx.update_global(|g: &mut AppConfig, _| {
+            g.export_profile = profile.clone();
+            g.save();
+        });
+    }
+
+    fn output_job(&self, cx: &gpui::App) -> Option<OutputJob> {
         let param = self.params.read(cx);
         if !param.all_some() {
             return None;
         }
         let path = param.path.as_ref().unwrap().clone();
-        let v_ix = param.video_stream_ix.unwrap();
-        let mut a_ix = param.audio_stream_ix.unwrap();
-        let range = param.selected_range.as_ref().unwrap().clone();
-        if let Some(ix) = self.audio_select.read(cx).selected_value() {
-            a_ix = *ix;
+        let ranges = param.export_ranges();
+        // keep the track order of source
+        let (a_ixs, gains): (Vec<usize>, Vec<f32>) = self
+            .audio_rails
+            .iter()
+            .zip(self.gain_inputs.iter())
+            .filter(|(r, _)| self.audio_selected.contains(&r.ix))
+            .map(|(r, input)| (r.ix, parse_gain(input.read(cx))))
+            .unzip();
+        // files without audio export video only
+        if a_ixs.is_empty() && !self.audio_rails.is_empty() {
+            return None;
+        }
+        let extra_ixs = self
+            .extra_streams
+            .iter()
+            .filter(|s| self.keep_kinds.contains(&s.kind))
+            .map(|s| s.ix)
+            .collect();
+        let batch_template = if self.batch && ranges.len() > 1 {
+            let template = self.template_input.read(cx).value().trim().to_string();
+            if template.is_empty() {
+                Some(DEFAULT_TEMPLATE.to_string())
+            } else {
+                Some(template)
+            }
+        } else {
+            None
+        };
+        let profile = self.export_profile(cx);
+        Some(OutputJob {
+            path,
+            out_path: self
+                .output_path
+                .with_extension(profile.container.extension()),
+            video_ix: param.video_stream_ix,
+            audio_ixs: a_ixs,
+            extra_ixs,
+            ranges,
+            profile,
+            batch_template,
+            audio_gains: self.mix_audio.then_some(gains),
+            markers: param.markers.clone(),
+        })
+    }
+
+    fn toggle_audio(&mut self, ix: usize, checked: bool, cx: &mut Context<Self>) {
+        if checked {
+            if !self.audio_selected.contains(&ix) {
+                self.audio_selected.push(ix);
+            }
+        } else {
+            self.audio_selected.retain(|i| *i != ix);
         }
-        Some((path, self.output_path.clone(), v_ix, a_ix, range))
+        cx.notify();
+    }
+
+    fn toggle_kind(&mut self, kind: StreamKind, checked: bool, cx: &mut Context<Self>) {
+        if checked {
+            if !self.keep_kinds.contains(&kind) {
+                self.keep_kinds.push(kind);
+            }
+        } else {
+            self.keep_kinds.retain(|k| *k != kind);
+        }
+        cx.notify();
+    }
+
+    /// reorder listed ranges, concat output follows this order
+    fn move_range(&mut self, from: usize, to: usize, cx: &mut Context<Self>) {
+        self.params.update(cx, |p, _| {
+            if from < p.ranges.len() && to < p.ranges.len() {
+                let range = p.ranges.remove(from);
+                p.ranges.insert(to, range);
+            }
+        });
+        if from < self.label_inputs.len() && to < self.label_inputs.len() {
+            let input = self.label_inputs.remove(from);
+            self.label_inputs.insert(to, input);
+        }
+        cx.notify();
+    }
+
+    fn remove_range(&mut self, ix: usize, cx: &mut Context<Self>) {
+        self.params.update(cx, |p, _| {
+            if ix < p.ranges.len() {
+                p.ranges.remove(ix);
+            }
+        });
+        if ix < self.label_inputs.len() {
+            self.label_inputs.remove(ix);
+        }
+        cx.notify();
+    }
+
+    /// keep typed labels on the listed ranges
+    fn apply_labels(&mut self, cx: &mut Context<Self>) {
+        let labels: Vec<String> = self
+            .label_inputs
+            .iter()
+            .map(|input| input.read(cx).value().trim().to_string())
+            .collect();
+        self.params.update(cx, |p, _| {
+            for (range, label) in p.ranges.iter_mut().zip(labels) {
+                range.label = label;
+            }
+        });
     }
 
     fn listen_path(this: &mut Self, _: &ClickEvent, _: &mut Window, cx: &mut Context<Self>) {
@@ -139,6 +442,14 @@ impl Render for OutputView {
             });
             self.update_path = false;
         }
+        let ranges = self.params.read(cx).ranges.clone();
+        let has_markers = !self.params.read(cx).markers.is_empty();
+        let multiple = self.params.read(cx).export_ranges().len() > 1;
+        if self.working {
+            cx.on_next_frame(w, |_, _, cx| {
+                cx.notify();
+            });
+        }
 
         div().w_full().v_flex().gap_3().child(
             div()
@@ -162,9 +473,38 @@ impl Render for OutputView {
                 )
                 .child(
                     div()
-                        .child(Label::new(t!("output.audio_track")))
-                        .child(Select::new(&self.audio_select)),
+                        .w_full()
+                        .child(Label::new(t!("output.container")))
+                        .child(Select::new(&self.container_select)),
                 )
+                .when(!ranges.is_empty(), |this| {
+                    this.child(range_list(&ranges, &self.label_inputs, cx))
+                })
+                .when(multiple, |this| {
+                    this.child(
+                        div()
+                            .w_full()
+                            .child(
+                                Checkbox::new("batch")
+                                    .label(t!("output.batch").to_string())
+                                    .checked(self.batch)
+                                    .on_click(cx.listener(|this, checked: &bool, _, cx| {
+                                        this.batch = *checked;
+                                        cx.notify();
+                                    })),
+                            )
+                            .when(self.batch, |this| {
+                                this.child(Label::new(t!("output.batch_template")))
+                                    .child(Input::new(&self.template_input))
+                            }),
+                    )
+                })
+                .when(!self.audio_rails.is_empty(), |this| {
+                    this.child(audio_tracks(self, cx))
+                })
+                .when(!self.extra_streams.is_empty(), |this| {
+                    this.child(extra_streams(self, cx))
+                })
                 .child(
                     div()
                         .w_full()
@@ -172,11 +512,325 @@ impl Render for OutputView {
                         .child(
                             Checkbox::new("checkbox")
                                 .label(t!("output.copy_stream").to_string())
-                                .checked(true)
-                                .disabled(true),
-                        ),
+                                .checked(self.copy_stream)
+                                .on_click(cx.listener(|this, checked: &bool, _, cx| {
+                                    this.copy_stream = *checked;
+                                    cx.notify();
+                                })),
+                        )
+                        .child(
+                            Checkbox::new("frame_accurate")
+                                .label(t!("output.frame_accurate").to_string())
+                                .checked(self.frame_accurate)
+                                .disabled(!self.copy_stream)
+                                .on_click(cx.listener(|this, checked: &bool, _, cx| {
+                                    this.frame_accurate = *checked;
+                                    cx.notify();
+                                })),
+                        )
+                        .child(
+                            Checkbox::new("strip_metadata")
+                                .label(t!("output.strip_metadata").to_string())
+                                .checked(self.strip_metadata)
+                                .on_click(cx.listener(|this, checked: &bool, _, cx| {
+                                    this.strip_metadata = *checked;
+                                    cx.notify();
+                                })),
+                        )
+                        .when(has_markers, |this| {
+                            this.child(
+                                Checkbox::new("markers_as_chapters")
+                                    .label(t!("output.markers_as_chapters").to_string())
+                                    .checked(self.markers_as_chapters)
+                                    .on_click(cx.listener(|this, checked: &bool, _, cx| {
+                                        this.markers_as_chapters = *checked;
+                                        cx.notify();
+                                    })),
+                            )
+                        }),
+                )
+                .when(!self.copy_stream, |this| {
+                    this.child(transcode_options(self))
+                })
+                .when(self.working, |this| {
+                    let value = f32::from_bits(self.progress.load(Ordering::Relaxed));
+                    this.child(
+                        div()
+                            .w_full()
+                            .h_flex()
+                            .gap_3()
+                            .child(Progress::new().value(value * 100.))
+                            .child(Label::new(format!("{:3.0}%", value * 100.))),
+                    )
+                }),
+        )
+    }
+}
+
+fn audio_tracks(view: &OutputView, cx: &mut Context<OutputView>) -> impl gpui::IntoElement {
+    let only_one = view.audio_selected.len() == 1;
+    div()
+        .w_full()
+        .child(Label::new(t!("output.audio_track")))
+        .children(
+            view.audio_rails
+                .iter()
+                .zip(view.gain_inputs.iter())
+                .map(|(rail, input)| {
+                    let ix = rail.ix;
+                    let checked = view.audio_selected.contains(&ix);
+                    div()
+                        .h_flex()
+                        .justify_between()
+                        .gap_3()
+                        .child(
+                            Checkbox::new(("audio-track", ix))
+                                .label(rail.title())
+                                .checked(checked)
+                                // at least one audio track is exported
+                                .disabled(checked && only_one)
+                                .on_click(cx.listener(move |this, checked: &bool, _, cx| {
+                                    this.toggle_audio(ix, *checked, cx);
+                                })),
+                        )
+                        .when(view.mix_audio && checked, |this| {
+                            this.child(gain_field(input))
+                        })
+                }),
+        )
+        .child(
+            Checkbox::new("mix-audio")
+                .label(t!("output.mix_audio").to_string())
+                .checked(view.mix_audio)
+                .on_click(cx.listener(|this, checked: &bool, _, cx| {
+                    this.mix_audio = *checked;
+                    cx.notify();
+                })),
+        )
+}
+
+fn extra_streams(view: &OutputView, cx: &mut Context<OutputView>) -> impl gpui::IntoElement {
+    let kinds = [
+        StreamKind::Subtitle,
+        StreamKind::Attachment,
+        StreamKind::Data,
+    ];
+    div()
+        .w_full()
+        .child(Label::new(t!("output.streams.title")))
+        .children(
+            kinds
+                .into_iter()
+                .filter(|kind| view.extra_streams.iter().any(|s| s.kind == *kind))
+                .map(|kind| {
+                    div()
+                        .w_full()
+                        .child(
+                            Checkbox::new(kind.i18n_key())
+                                .label(t!(kind.i18n_key()).to_string())
+                                .checked(view.keep_kinds.contains(&kind))
+                                .on_click(cx.listener(move |this, checked: &bool, _, cx| {
+                                    this.toggle_kind(kind, *checked, cx);
+                                })),
+                        )
+                        .children(
+                            view.extra_streams
+                                .iter()
+                                .filter(|s| s.kind == kind)
+                                .map(|s| {
+                                    let name = s
+                                        .name
+                                        .as_ref()
+                                        .map_or(String::new(), |n| format!(" ({})", n));
+                                    div()
+                                        .pl_6()
+                                        .child(Label::new(format!("#{} {}{}", s.ix, s.codec, name)))
+                                }),
+                        )
+                }),
+        )
+}
+
+fn range_list(
+    ranges: &[ClipRange],
+    label_inputs: &[Entity<InputState>],
+    cx: &mut Context<OutputView>,
+) -> impl gpui::IntoElement {
+    let last = ranges.len() - 1;
+    div()
+        .w_full()
+        .child(Label::new(t!("output.ranges")))
+        .children(
+            ranges
+                .iter()
+                .zip(label_inputs)
+                .enumerate()
+                .map(|(i, (range, label))| {
+                    div()
+                        .h_flex()
+                        .justify_between()
+                        .child(Label::new(format!(
+                            "#{}  {} - {}",
+                            i + 1,
+                            format_sec(range.start),
+                            format_sec(range.end)
+                        )))
+                        .child(
+                            div()
+                                .h_flex()
+                                .child(div().w(px(160.)).child(Input::new(label)))
+                                .child(
+                                    Button::new(("range-up", i))
+                                        .ghost()
+                                        .small()
+                                        .icon(IconName::ArrowUp)
+                                        .disabled(i == 0)
+                                        .on_click(cx.listener(move |this, _, _, cx| {
+                                            this.move_range(i, i.saturating_sub(1), cx);
+                                        })),
+                                )
+                                .child(
+                                    Button::new(("range-down", i))
+                                        .ghost()
+                                        .small()
+                                        .icon(IconName::ArrowDown)
+                                        .disabled(i == last)
+                                        .on_click(cx.listener(move |this, _, _, cx| {
+                                            this.move_range(i, i + 1, cx);
+                                        })),
+                                )
+                                .child(
+                                    Button::new(("range-remove", i))
+                                        .ghost()
+                                        .small()
+                                        .icon(IconName::Close)
+                                        .on_click(cx.listener(move |this, _, _, cx| {
+                                            this.remove_range(i, cx);
+                                        })),
+                                ),
+                        )
+                }),
+        )
+}
+
+fn transcode_options(view: &OutputView) -> impl gpui::IntoElement {
+    let (quality_label, quality_input) = match view.rate_control {
+        RateControl::Crf => (t!("output.crf"), &view.crf_input),
+        RateControl::Bitrate => (t!("output.video_bitrate"), &view.video_bitrate_input),
+    };
+
+    div()
+        .v_flex()
+        .gap_3()
+        .child(
+            div()
+                .h_flex()
+                .gap_3()
+                .child(
+                    div()
+                        .flex_1()
+                        .child(Label::new(t!("output.video_codec")))
+                        .child(Select::new(&view.video_codec_select)),
+                )
+                .child(
+                    div()
+                        .flex_1()
+                        .child(Label::new(t!("output.audio_codec")))
+                        .child(Select::new(&view.audio_codec_select)),
                 ),
         )
+        .child(
+            div()
+                .h_flex()
+                .gap_3()
+                .child(
+                    div()
+                        .flex_1()
+                        .child(Label::new(t!("output.rate_control.title")))
+                        .child(Select::new(&view.rate_control_select)),
+                )
+                .child(
+                    div()
+                        .flex_1()
+                        .child(Label::new(quality_label))
+                        .child(Input::new(quality_input)),
+                )
+                .child(
+                    div()
+                        .flex_1()
+                        .child(Label::new(t!("output.audio_bitrate")))
+                        .child(Input::new(&view.audio_bitrate_input)),
+                ),
+        )
+}
+
+fn enum_select<T>(
+    current: T,
+    window: &mut Window,
+    cx: &mut Context<OutputView>,
+) -> Entity<SelectState<Vec<T>>>
+where
+    T: SelectItem + IntoEnumIterator + PartialEq + 'static,
+{
+    let items: Vec<T> = T::iter().collect();
+    let selected_index = items.iter().position(|i| *i == current).map(IndexPath::new);
+    cx.new(|cx| SelectState::new(items, selected_index, window, cx))
+}
+
+fn number_input(
+    value: impl ToString,
+    window: &mut Window,
+    cx: &mut Context<OutputView>,
+) -> Entity<InputState> {
+    let value = value.to_string();
+    cx.new(|cx| InputState::new(window, cx).default_value(value))
+}
+
+impl SelectItem for Container {
+    type Value = Self;
+
+    fn title(&self) -> SharedString {
+        self.label().into()
+    }
+
+    fn value(&self) -> &Self::Value {
+        self
+    }
+}
+
+impl SelectItem for VideoCodec {
+    type Value = Self;
+
+    fn title(&self) -> SharedString {
+        self.label().into()
+    }
+
+    fn value(&self) -> &Self::Value {
+        self
+    }
+}
+
+impl SelectItem for AudioCodec {
+    type Value = Self;
+
+    fn title(&self) -> SharedString {
+        self.label().into()
+    }
+
+    fn value(&self) -> &Self::Value {
+        self
+    }
+}
+
+impl SelectItem for RateControl {
+    type Value = Self;
+
+    fn title(&self) -> SharedString {
+        t!(self.i18n_key()).to_string().into()
+    }
+
+    fn value(&self) -> &Self::Value {
+        self
     }
 }
 
@@ -198,8 +852,18 @@ pub fn build_output_dialog(
                 .child(
                     Button::new("cancel")
                         .label(t!("common.actions.cancel"))
-                        .disabled(output_view_for_action.read(cx).working)
-                        .on_click(|_, window, cx| window.close_dialog(cx)),
+                        .on_click({
+                            let output_view = output_view_for_action.clone();
+                            move |_, window, cx| {
+                                // stop running export, dialog closes when it returns
+                                let cancelled = output_view.update(cx, |view, _| {
+                                    view.cancel.as_ref().map(|c| c.cancel()).is_some()
+                                });
+                                if !cancelled {
+                                    window.close_dialog(cx);
+                                }
+                            }
+                        }),
                 )
                 .child(
                     Button::new("output")
@@ -208,35 +872,43 @@ pub fn build_output_dialog(
                         .disabled(output_view_for_action.read(cx).working)
                         .on_click(move |_, window, cx| {
                             let job = output_view_for_action.update(cx, |view, cx| {
+                                if view.working {
+                                    return None;
+                                }
                                 let job = view.output_job(cx);
-                                view.working = job.is_some();
-                                job
+                                if let Some(job) = job.as_ref() {
+                                    view.save_profile(&job.profile, cx);
+                                    view.working = true;
+                                    view.cancel = Some(CancelToken::new());
+                                    view.progress.store(0, Ordering::Relaxed);
+                                }
+                                cx.notify();
+                                job.map(|job| (job, view.cancel.clone(), view.progress.clone()))
                             });
-                            if let Some((input_path, output_path, video_ix, audio_ix, range)) = job
-                            {
+                            if let Some((job, Some(cancel), progress)) = job {
                                 let window_handle = window.window_handle();
+                                let output_view = output_view_for_action.clone();
                                 cx.spawn(async move |cx| {
-                                    let result = cx
+                                    let results = cx
                                         .background_spawn(async move {
-                                            output(
-                                                &input_path,
-                                                &output_path,
-                                                video_ix,
-                                                audio_ix,
-                                                &range,
-                                            )
+                                            job.run(&cancel, |value| {
+                                                progress.store(
+                                                    (value as f32).to_bits(),
+                                                    Ordering::Relaxed,
+                                                );
+                                            })
                                         })
                                         .await;
+                                    // next export runs with a fresh token
+                                    let _ = output_view.update(cx, |view, cx| {
+                                        view.working = false;
+                                        view.cancel = None;
+                                        cx.notify();
+                                    });
 
                                     let _ = cx.update_window(window_handle, |_, window, cx| {
                                         window.close_dialog(cx);
-                                        let notification = match result {
-                                            Ok(()) => Notification::success(t!("output.finished")),
-                                            Err(error) => Notification::error(t!(
-                                                "output.failed",
-                                                error = error
-                                            )),
-                                        };
+                                        let notification = result_notification(results);
                                         cx.defer(move |cx| {
                                             window_handle
                                                 .update(cx, |_, w, cx| {
@@ -255,3 +927,59 @@ pub fn build_output_dialog(
                 ),
         )
 }
+
+/// one notification for all exported files
+fn result_notification(results: Vec<(PathBuf, anyhow::Result<Vec<String>>)>) -> Notification {
+    if results
+        .iter()
+        .any(|(_, r)| r.as_ref().is_err_and(|e| e.is::<Cancelled>()))
+    {
+        return Notification::warning(t!("output.cancelled"));
+    }
+
+    let total = results.len();
+    let mut notices = vec![];
+    let failed: Vec<String> = results
+        .into_iter()
+        .filter_map(|(path, result)| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let error = match result {
+                Ok(notes) if total == 1 => {
+                    notices.extend(notes);
+                    return None;
+                }
+                Ok(notes) => {
+                    notices.extend(notes.iter().map(|n| format!("{}: {}", name, n)));
+                    return None;
+                }
+                Err(error) => error,
+            };
+            if total == 1 {
+                return Some(error.to_string());
+            }
+            Some(format!("{}: {}", name, error))
+        })
+        .collect();
+
+    if failed.is_empty() {
+        if !notices.is_empty() {
+            Notification::warning(t!(
+                "output.finished_with_notice",
+                notice = notices.join("\n")
+            ))
+        } else if total == 1 {
+            Notification::success(t!("output.finished"))
+        } else {
+            Notification::success(t!("output.batch_finished", count = total))
+        }
+    } else if total == 1 {
+        Notification::error(t!("output.failed", error = failed[0]))
+    } else {
+        Notification::error(t!(
+            "output.batch_failed",
+            failed = failed.len(),
+            total = total,
+            error = failed.join("\n")
+        ))
+    }
+}